@@ -0,0 +1,75 @@
+//! Builds the BVH of `random_scene()` with every split method and compares the
+//! resulting trees and how fast they trace a fixed batch of random rays.
+//!
+//!     cargo run --release --example bvh_stats
+
+use ray_tracing::bvh::{BvhBuilder, SplitMethod};
+use ray_tracing::hittable::Hittable;
+use ray_tracing::ray::Ray;
+use ray_tracing::scenes::random_scene;
+use ray_tracing::vec3::Point3;
+
+use rand::prelude::*;
+use std::time::Instant;
+
+const RAY_COUNT: usize = 1_000_000;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let world = random_scene(&mut rng);
+
+    let rays: Vec<Ray> = (0..RAY_COUNT)
+        .map(|_| {
+            let origin = Point3::new(13.0, 2.0, 3.0);
+            let target = Point3::new(rng.gen_range(-11.0..11.0), 0.0, rng.gen_range(-11.0..11.0));
+            Ray::new(origin, target - origin)
+        })
+        .collect();
+
+    println!(
+        "{:<16} {:>9} {:>7} {:>7} {:>6} {:>10} {:>9} {:>10} {:>10}",
+        "split", "nodes", "leaves", "depth", "leaf", "avg leaf", "SAH", "build ms", "Mrays/s"
+    );
+
+    let builders = [
+        (
+            "sah (12 bins)",
+            BvhBuilder::new(SplitMethod::Sah { bins: 12 }),
+        ),
+        (
+            "sah (32 bins)",
+            BvhBuilder::new(SplitMethod::Sah { bins: 32 }),
+        ),
+        ("equal counts", BvhBuilder::new(SplitMethod::EqualCounts)),
+        ("middle", BvhBuilder::new(SplitMethod::Middle)),
+    ];
+
+    for (name, builder) in builders {
+        let start = Instant::now();
+        let (bvh, stats) = builder.build(&world);
+        let build_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut hits = 0;
+        for r in &rays {
+            if bvh.hit(*r, 0.001, f64::INFINITY).is_some() {
+                hits += 1;
+            }
+        }
+        let trace_time = start.elapsed();
+        assert!(hits > 0);
+
+        println!(
+            "{:<16} {:>9} {:>7} {:>7} {:>6} {:>10.2} {:>9.2} {:>10.2} {:>10.2}",
+            name,
+            stats.node_count,
+            stats.leaf_count,
+            stats.max_depth,
+            stats.largest_leaf,
+            stats.average_leaf_size(),
+            stats.sah_cost,
+            build_time.as_secs_f64() * 1000.0,
+            RAY_COUNT as f64 / trace_time.as_secs_f64() / 1e6,
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Traces a fixed batch of random rays through `random_scene()`, both as a flat list and
+//! through a BVH, and reports the heap allocations, reference count updates and closer
+//! hits each ray costs.
+//!
+//! The `owned` rows replay the hit records from before they borrowed their material:
+//! records owned an `Arc<dyn Material>`, a default record allocated a fresh material,
+//! every primitive hit cloned its material and lists cloned the record on every closer
+//! hit. The `borrowed` rows use [`Hittable::hit`].
+//!
+//!     cargo run --release --example hit_costs
+
+use ray_tracing::aabb::Aabb;
+use ray_tracing::bvh::BvhNode;
+use ray_tracing::hittable::{HitRecord, Hittable};
+use ray_tracing::hittable_list::HittableList;
+use ray_tracing::material::{Lambertian, Material};
+use ray_tracing::ray::Ray;
+use ray_tracing::scenes::random_scene;
+use ray_tracing::vec3::Point3;
+
+use rand::prelude::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+const RAY_COUNT: usize = 200_000;
+
+/// Counts every allocation made by the process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+thread_local! {
+    static HITS: Cell<usize> = const { Cell::new(0) };
+    static REFCOUNT_UPDATES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count(counter: &'static std::thread::LocalKey<Cell<usize>>) {
+    counter.with(|count| count.set(count.get() + 1));
+}
+
+/// Counts the hits of the object it wraps.
+struct Counted(Arc<dyn Hittable + Send + Sync>);
+
+impl Hittable for Counted {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = self.0.hit(r, t_min, t_max);
+        if hit.is_some() {
+            count(&HITS);
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+}
+
+/// An `Arc<dyn Material>` counting the atomic updates of its reference count: one when
+/// it is cloned and one when it is dropped.
+struct SharedMaterial(Arc<dyn Material + Send + Sync>);
+
+impl Clone for SharedMaterial {
+    fn clone(&self) -> Self {
+        count(&REFCOUNT_UPDATES);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for SharedMaterial {
+    fn drop(&mut self) {
+        count(&REFCOUNT_UPDATES);
+    }
+}
+
+/// The hit record as it was, owning a reference to its material, reduced to what the
+/// costs depend on.
+#[derive(Clone)]
+struct OwnedHitRecord {
+    t: f64,
+    material: SharedMaterial,
+}
+
+impl Default for OwnedHitRecord {
+    fn default() -> Self {
+        Self {
+            t: 0.0,
+            material: SharedMaterial(Arc::new(Lambertian::default())),
+        }
+    }
+}
+
+/// The `Hittable` trait as it was, filling in a record and telling whether it did.
+trait OwnedHittable {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut OwnedHitRecord) -> bool;
+}
+
+/// A primitive of the scene that clones its material into every record it fills in.
+struct OwnedPrimitive {
+    object: Arc<dyn Hittable + Send + Sync>,
+    /// Stands in for the material of the object, only its reference count matters.
+    material: SharedMaterial,
+}
+
+impl OwnedHittable for OwnedPrimitive {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut OwnedHitRecord) -> bool {
+        let Some(hit) = self.object.hit(r, t_min, t_max) else {
+            return false;
+        };
+
+        rec.t = hit.t;
+        rec.material = self.material.clone();
+
+        true
+    }
+}
+
+struct OwnedList(Vec<OwnedPrimitive>);
+
+impl OwnedHittable for OwnedList {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut OwnedHitRecord) -> bool {
+        let mut temp_rec = OwnedHitRecord::default();
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for object in &self.0 {
+            if object.hit(r, t_min, closest_so_far, &mut temp_rec) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+
+                *rec = temp_rec.clone();
+            }
+        }
+
+        hit_anything
+    }
+}
+
+/// A BVH split at the median of the centroids along their longest axis.
+enum OwnedBvh {
+    Leaf(OwnedPrimitive),
+    Node {
+        bbox: Aabb,
+        left: Box<OwnedBvh>,
+        right: Box<OwnedBvh>,
+    },
+}
+
+impl OwnedBvh {
+    fn new(mut primitives: Vec<(Aabb, OwnedPrimitive)>) -> Self {
+        if primitives.len() == 1 {
+            return OwnedBvh::Leaf(primitives.pop().unwrap().1);
+        }
+
+        let bbox = primitives
+            .iter()
+            .map(|(bbox, _)| *bbox)
+            .reduce(Aabb::surrounding_box)
+            .unwrap();
+        let axis = primitives
+            .iter()
+            .map(|(bbox, _)| Aabb::new(bbox.centroid(), bbox.centroid()))
+            .reduce(Aabb::surrounding_box)
+            .unwrap()
+            .longest_axis();
+        primitives.sort_by(|(a, _), (b, _)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+
+        let right = primitives.split_off(primitives.len() / 2);
+        OwnedBvh::Node {
+            bbox,
+            left: Box::new(OwnedBvh::new(primitives)),
+            right: Box::new(OwnedBvh::new(right)),
+        }
+    }
+}
+
+impl OwnedHittable for OwnedBvh {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut OwnedHitRecord) -> bool {
+        match self {
+            OwnedBvh::Leaf(primitive) => primitive.hit(r, t_min, t_max, rec),
+            OwnedBvh::Node { bbox, left, right } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return false;
+                }
+
+                let hit_left = left.hit(r, t_min, t_max, rec);
+                let hit_right = right.hit(r, t_min, if hit_left { rec.t } else { t_max }, rec);
+
+                hit_left || hit_right
+            }
+        }
+    }
+}
+
+/// Traces a ray and tells whether it hit anything.
+type TraceRay<'a> = dyn Fn(Ray) -> bool + 'a;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let mut world = HittableList::new();
+    for object in random_scene(&mut rng).objects {
+        world.add(Arc::new(Counted(object)));
+    }
+    let bvh = BvhNode::new(&world);
+
+    let material = SharedMaterial(Arc::new(Lambertian::default()));
+    let owned_primitives = || {
+        world.objects.iter().map(|object| OwnedPrimitive {
+            object: object.clone(),
+            material: material.clone(),
+        })
+    };
+    let owned_world = OwnedList(owned_primitives().collect());
+    let owned_bvh = OwnedBvh::new(
+        owned_primitives()
+            .map(|primitive| (primitive.object.bounding_box().unwrap(), primitive))
+            .collect(),
+    );
+
+    let rays: Vec<Ray> = (0..RAY_COUNT)
+        .map(|_| {
+            let origin = Point3::new(13.0, 2.0, 3.0);
+            let target = Point3::new(rng.gen_range(-11.0..11.0), 0.0, rng.gen_range(-11.0..11.0));
+            Ray::new(origin, target - origin)
+        })
+        .collect();
+
+    println!(
+        "{:<6} {:<9} {:>12} {:>12} {:>14} {:>10} {:>10}",
+        "world", "records", "allocs/ray", "bytes/ray", "refcounts/ray", "hits/ray", "Mrays/s"
+    );
+
+    // Callers made a default record for every ray.
+    let owned_hit = |world: &dyn OwnedHittable, r: Ray| {
+        let mut rec = OwnedHitRecord::default();
+        world.hit(r, 0.001, f64::INFINITY, &mut rec)
+    };
+    let borrowed_hit = |world: &dyn Hittable, r: Ray| world.hit(r, 0.001, f64::INFINITY).is_some();
+
+    let runs: [(&str, &str, &TraceRay); 4] = [
+        ("list", "owned", &|r| owned_hit(&owned_world, r)),
+        ("list", "borrowed", &|r| borrowed_hit(&world, r)),
+        ("bvh", "owned", &|r| owned_hit(&owned_bvh, r)),
+        ("bvh", "borrowed", &|r| borrowed_hit(&bvh, r)),
+    ];
+    for (world, records, hit) in runs {
+        HITS.with(|hits| hits.set(0));
+        REFCOUNT_UPDATES.with(|updates| updates.set(0));
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+
+        let start = Instant::now();
+        let misses = rays.iter().filter(|&&r| !hit(r)).count();
+        let elapsed = start.elapsed();
+        assert_eq!(misses, 0);
+
+        let per_ray = |count: usize| count as f64 / RAY_COUNT as f64;
+        println!(
+            "{:<6} {:<9} {:>12.2} {:>12.2} {:>14.2} {:>10.2} {:>10.2}",
+            world,
+            records,
+            per_ray(ALLOCATIONS.load(Ordering::Relaxed) - allocations),
+            per_ray(ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes),
+            per_ray(REFCOUNT_UPDATES.with(Cell::get)),
+            per_ray(HITS.with(Cell::get)),
+            RAY_COUNT as f64 / elapsed.as_secs_f64() / 1e6,
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub minimum: Point3,
+    pub maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point3, maximum: Point3) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn hit(&self, r: Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction[a];
+            let mut t0 = (self.minimum[a] - r.origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin[a]) * inv_d;
+
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+        let small = Point3::new(
+            box0.minimum.x.min(box1.minimum.x),
+            box0.minimum.y.min(box1.minimum.y),
+            box0.minimum.z.min(box1.minimum.z),
+        );
+
+        let big = Point3::new(
+            box0.maximum.x.max(box1.maximum.x),
+            box0.maximum.y.max(box1.maximum.y),
+            box0.maximum.z.max(box1.maximum.z),
+        );
+
+        Aabb::new(small, big)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn aabb_hit() {
+        let bbox = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+
+        let towards = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let away = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        let beside = Ray::new(Point3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(bbox.hit(towards, 0.0, f64::INFINITY));
+        assert!(!bbox.hit(away, 0.0, f64::INFINITY));
+        assert!(!bbox.hit(beside, 0.0, f64::INFINITY));
+        assert!(!bbox.hit(towards, 0.0, 3.0));
+    }
+
+    #[test]
+    fn aabb_hit_axis_parallel_ray() {
+        let bbox = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+
+        let inside_slab = Ray::new(Point3::new(-5.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let outside_slab = Ray::new(Point3::new(-5.0, 1.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+
+        assert!(bbox.hit(inside_slab, 0.0, f64::INFINITY));
+        assert!(!bbox.hit(outside_slab, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn aabb_surrounding_box() {
+        let a = Aabb::new(Point3::new(-1.0, 0.0, 2.0), Point3::new(1.0, 1.0, 3.0));
+        let b = Aabb::new(Point3::new(0.0, -2.0, 0.0), Point3::new(0.5, 4.0, 1.0));
+
+        assert_eq!(
+            Aabb::surrounding_box(a, b),
+            Aabb::new(Point3::new(-1.0, -2.0, 0.0), Point3::new(1.0, 4.0, 3.0))
+        );
+    }
+}
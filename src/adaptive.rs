@@ -0,0 +1,121 @@
+//! Adaptive sampling: pixels stop taking samples once their average is known well enough,
+//! so the time goes to the noisy ones.
+//!
+//! Every pixel tracks the variance of the luminance of its samples. It converges when the
+//! standard error of its average, the noise left in the image, falls below a fraction of
+//! that average.
+
+/// When the pixels of a render have enough samples. The samples per pixel of the render
+/// are the most any pixel takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel takes before it can converge, and between two checks of the
+    /// pixels in single pass renders.
+    pub min_samples: u32,
+    /// The standard error of a converged pixel, relative to its luminance.
+    pub threshold: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            threshold: 0.02,
+        }
+    }
+}
+
+/// Pixels darker than this are held to the error allowed at this luminance, or the
+/// black ones would never converge.
+const MIN_LUMINANCE: f64 = 0.01;
+
+impl AdaptiveSampling {
+    /// Whether the pixel whose sample luminances are `samples` needs no more of them.
+    pub(crate) fn converged(&self, samples: &RunningVariance) -> bool {
+        if samples.count < self.min_samples.max(2) {
+            return false;
+        }
+
+        samples.standard_error() <= self.threshold * samples.mean.max(MIN_LUMINANCE)
+    }
+}
+
+/// The mean and variance of a stream of values, updated one value at a time with
+/// Welford's algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct RunningVariance {
+    count: u32,
+    mean: f64,
+    /// Sum of the squared differences to the mean.
+    m2: f64,
+}
+
+impl RunningVariance {
+    pub(crate) fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// The unbiased sample variance, 0 before there are two values.
+    pub(crate) fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+
+        self.m2 / (self.count - 1) as f64
+    }
+
+    /// The standard deviation of the mean of the values.
+    fn standard_error(&self) -> f64 {
+        (self.variance() / self.count as f64).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_variance(values: &[f64]) -> RunningVariance {
+        let mut variance = RunningVariance::default();
+        for &value in values {
+            variance.add(value);
+        }
+
+        variance
+    }
+
+    #[test]
+    fn tracks_the_mean_and_variance() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let variance = running_variance(&values);
+
+        assert_eq!(variance.mean, 5.0);
+        assert!((variance.variance() - 32.0 / 7.0).abs() < 1e-12);
+        assert_eq!(running_variance(&[3.0]).variance(), 0.0);
+
+        // Large offsets do not cancel the digits of small differences.
+        let shifted: Vec<f64> = values.iter().map(|value| value + 1e9).collect();
+        assert!((running_variance(&shifted).variance() - 32.0 / 7.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn converges_once_the_error_is_small_enough() {
+        let adaptive = AdaptiveSampling {
+            min_samples: 4,
+            threshold: 0.05,
+        };
+
+        // Flat pixels converge after the minimum, black ones too.
+        assert!(!adaptive.converged(&running_variance(&[0.5; 3])));
+        assert!(adaptive.converged(&running_variance(&[0.5; 4])));
+        assert!(adaptive.converged(&running_variance(&[0.0; 4])));
+
+        // Noisy ones once there are enough samples for their error.
+        let noisy = |count: usize| running_variance(&[0.0, 1.0].repeat(count / 2));
+        assert!(!adaptive.converged(&noisy(16)));
+        assert!(!adaptive.converged(&noisy(256)));
+        assert!(adaptive.converged(&noisy(512)));
+    }
+}
@@ -0,0 +1,262 @@
+//! Arbitrary output variables: passes describing what the camera rays hit first, taken
+//! from the same rays as the beauty image, for denoising and compositing.
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::renderer::Framebuffer;
+use crate::vec3::Color;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Aov {
+    /// Color of the surface, without lighting.
+    Albedo,
+    /// World space shading normal, facing the camera.
+    Normal,
+    /// World space position.
+    Position,
+    /// Distance along the viewing direction, infinite where nothing was hit.
+    Depth,
+    /// Index of the object in the world, from 1. 0 is the background.
+    ObjectId,
+    /// Materials numbered from 1 in the order the objects of the world hold them. 0 is the
+    /// background.
+    MaterialId,
+    /// Samples taken for the pixel, which vary with adaptive sampling.
+    SampleCount,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Depth,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::SampleCount,
+    ];
+
+    pub const NAMES: [&'static str; 7] = [
+        "albedo",
+        "normal",
+        "position",
+        "depth",
+        "object_id",
+        "material_id",
+        "samples",
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let index = Self::NAMES.iter().position(|&known| known == name)?;
+
+        Some(Self::ALL[index])
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Whether the pass comes from the first hits of the camera rays, which renders only
+    /// record with [`Renderer::aovs`](crate::renderer::Renderer::aovs). The sample counts
+    /// are known either way.
+    pub fn needs_first_hits(self) -> bool {
+        self != Aov::SampleCount
+    }
+
+    /// The channels of the pass in an OpenEXR file. They hold the first components of the
+    /// pixels of its framebuffer.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::SampleCount => &["count"],
+        }
+    }
+}
+
+/// The first hits of the camera rays of a pixel, added up.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct AovSums {
+    albedo: Color,
+    normal: Color,
+    position: Color,
+    depth: f64,
+    hits: u32,
+    // Ids cannot be averaged, they come from the first sample of the pixel that hits.
+    object_id: u32,
+    material_id: u32,
+}
+
+impl AovSums {
+    /// Adds the first hit of a camera ray, `depth` away from the camera, on a material
+    /// numbered `material_id`. Misses add nothing but still count as samples of the albedo
+    /// and normal.
+    pub(crate) fn add(&mut self, rec: &HitRecord, depth: f64, material_id: u32) {
+        if self.hits == 0 {
+            self.object_id = rec.object_id;
+            self.material_id = material_id;
+        }
+
+        self.albedo = self.albedo + rec.material.albedo(rec);
+        self.normal = self.normal + rec.normal;
+        self.position = self.position + rec.p;
+        self.depth += depth;
+        self.hits += 1;
+    }
+}
+
+/// The materials of a world, numbered from 1 in the order its objects hold them, so the
+/// ids do not depend on the image.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MaterialIds(HashMap<usize, u32>);
+
+impl MaterialIds {
+    pub(crate) fn new(world: &dyn Hittable) -> Self {
+        let mut ids = HashMap::new();
+        world.for_each_material(&mut |material| {
+            let next_id = ids.len() as u32 + 1;
+            ids.entry(address(material)).or_insert(next_id);
+        });
+
+        Self(ids)
+    }
+
+    /// The id of `material`, 0 if it is not in the world.
+    pub(crate) fn get(&self, material: &dyn Material) -> u32 {
+        self.0.get(&address(material)).copied().unwrap_or(0)
+    }
+}
+
+/// Tells materials apart: the world holds them in `Arc`s, so they stay where they are.
+fn address(material: &dyn Material) -> usize {
+    material as *const dyn Material as *const () as usize
+}
+
+/// The passes of a rendered image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aovs {
+    passes: [Option<Framebuffer>; 7],
+}
+
+impl Aovs {
+    /// The passes of a `width` by `height` image from the sample counts of its pixels,
+    /// given row by row from the top, and the sums of their first hits if recorded.
+    pub(crate) fn from_sums(
+        width: u32,
+        height: u32,
+        sums: Option<&[AovSums]>,
+        samples: &[u32],
+    ) -> Self {
+        let mut passes = match sums {
+            Some(sums) => first_hit_passes(width, height, sums, samples).map(Some),
+            None => Aov::ALL.map(|_| None),
+        };
+
+        let mut sample_counts = Framebuffer::new(width, height);
+        for (i, &samples) in samples.iter().enumerate() {
+            sample_counts.set(i as u32 % width, i as u32 / width, grey(samples as f64));
+        }
+        passes[Aov::SampleCount as usize] = Some(sample_counts);
+
+        Self { passes }
+    }
+
+    /// The pass `aov`, `None` if it [needs the first hits](Aov::needs_first_hits) and the
+    /// render did not record them.
+    pub fn get(&self, aov: Aov) -> Option<&Framebuffer> {
+        self.passes[aov as usize].as_ref()
+    }
+
+    /// The pass brought into the displayable range, for 8 bit formats: normals mapped to
+    /// `[0, 1]`, depths divided by the largest one, ids shown as random colors and sample
+    /// counts as a heatmap from black to white.
+    pub fn preview(&self, aov: Aov) -> Option<Framebuffer> {
+        let pass = self.get(aov)?;
+        let largest = pass
+            .pixels()
+            .iter()
+            .map(|value| value.x)
+            .filter(|value| value.is_finite())
+            .fold(0.0, f64::max);
+
+        let mut preview = Framebuffer::new(pass.width(), pass.height());
+        for y in 0..pass.height() {
+            for x in 0..pass.width() {
+                let value = pass.get(x, y);
+
+                let color = match aov {
+                    Aov::Albedo => value,
+                    Aov::Normal => 0.5 * (value + Color::new(1.0, 1.0, 1.0)),
+                    Aov::Position => value,
+                    Aov::Depth if largest > 0.0 => value / largest,
+                    Aov::Depth => value,
+                    Aov::ObjectId | Aov::MaterialId => id_color(value.x as u32),
+                    Aov::SampleCount if largest > 0.0 => heat(value.x / largest),
+                    Aov::SampleCount => Color::default(),
+                };
+                preview.set(x, y, color);
+            }
+        }
+
+        Some(preview)
+    }
+}
+
+/// Averages the first hits of the pixels into their passes, leaving the sample counts
+/// black.
+fn first_hit_passes(
+    width: u32,
+    height: u32,
+    sums: &[AovSums],
+    samples: &[u32],
+) -> [Framebuffer; 7] {
+    let mut passes = Aov::ALL.map(|_| Framebuffer::new(width, height));
+
+    for (i, (sums, &samples)) in sums.iter().zip(samples).enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let mut set = |aov: Aov, color: Color| passes[aov as usize].set(x, y, color);
+
+        if sums.hits == 0 {
+            set(Aov::Depth, grey(f64::INFINITY));
+            continue;
+        }
+
+        let (samples, hits) = (samples as f64, sums.hits as f64);
+        set(Aov::Albedo, sums.albedo / samples);
+        set(Aov::Normal, sums.normal / samples);
+        set(Aov::Position, sums.position / hits);
+        set(Aov::Depth, grey(sums.depth / hits));
+        set(Aov::ObjectId, grey(sums.object_id as f64));
+        set(Aov::MaterialId, grey(sums.material_id as f64));
+    }
+
+    passes
+}
+
+fn grey(value: f64) -> Color {
+    Color::new(value, value, value)
+}
+
+/// Black through red and yellow to white as `t` goes from 0 to 1.
+fn heat(t: f64) -> Color {
+    let ramp = |start: f64| (3.0 * t - start).clamp(0.0, 1.0);
+
+    Color::new(ramp(0.0), ramp(1.0), ramp(2.0))
+}
+
+/// A color telling `id` apart from its neighbours, black for the background.
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::default();
+    }
+
+    // Fibonacci hashing spreads consecutive ids over the whole range.
+    let hash = id.wrapping_mul(0x9e37_79b9);
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.0;
+
+    Color::new(channel(24), channel(16), channel(8))
+}
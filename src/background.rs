@@ -0,0 +1,47 @@
+use crate::environment::EnvironmentMap;
+use crate::vec3::{Color, Vec3};
+
+use std::sync::Arc;
+
+/// What a ray sees when it leaves the scene without hitting anything.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Black,
+    Solid(Color),
+    /// Blends from `horizon` looking down to `zenith` looking up.
+    Gradient {
+        horizon: Color,
+        zenith: Color,
+    },
+    Environment(Arc<EnvironmentMap>),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+impl Background {
+    /// The white to light blue sky gradient.
+    pub fn sky() -> Self {
+        Background::Gradient {
+            horizon: Color::new(1.0, 1.0, 1.0),
+            zenith: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn value(&self, direction: Vec3) -> Color {
+        match self {
+            Background::Black => Color::default(),
+            Background::Solid(color) => *color,
+            Background::Gradient { horizon, zenith } => {
+                let unit_direction = direction.unit_vector();
+                let t = 0.5 * (unit_direction.y + 1.0);
+
+                (1.0 - t) * *horizon + t * *zenith
+            }
+            Background::Environment(map) => map.value(direction),
+        }
+    }
+}
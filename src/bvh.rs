@@ -0,0 +1,508 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::{self, HittableList};
+use crate::material::Material;
+use crate::ray::Ray;
+
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct BvhNode {
+    pub left: Arc<dyn Hittable + Send + Sync>,
+    pub right: Arc<dyn Hittable + Send + Sync>,
+    pub bbox: Aabb,
+}
+
+impl BvhNode {
+    /// Builds a hierarchy over every object of `list` with the default [`BvhBuilder`].
+    ///
+    /// Panics if the list is empty or if one of its objects has no bounding box.
+    pub fn new(list: &HittableList) -> Self {
+        BvhBuilder::default().build(list).0
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max);
+        let hit_right = self
+            .right
+            .hit(r, t_min, hit_left.map_or(t_max, |rec| rec.t));
+
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    fn add_lights(&self, lights: &mut HittableList) {
+        hittable_list::add_lights(&self.left, lights);
+        hittable_list::add_lights(&self.right, lights);
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        self.left.for_each_material(f);
+        self.right.for_each_material(f);
+    }
+}
+
+/// How a node's primitives are divided between its two children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitMethod {
+    /// Surface area heuristic evaluated at `bins` evenly spaced candidate planes.
+    Sah { bins: usize },
+    /// Median split: both children get the same number of primitives.
+    EqualCounts,
+    /// Split at the midpoint of the centroid bounds.
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhBuilder {
+    pub split_method: SplitMethod,
+    pub max_leaf_size: usize,
+    /// Relative cost of visiting an interior node, used by the SAH.
+    pub traversal_cost: f64,
+    /// Relative cost of intersecting one primitive, used by the SAH.
+    pub intersection_cost: f64,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self::new(SplitMethod::Sah { bins: 12 })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BvhStats {
+    pub primitive_count: usize,
+    pub node_count: usize,
+    pub interior_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub smallest_leaf: usize,
+    pub largest_leaf: usize,
+    /// Expected cost of tracing a random ray through the tree, relative to the root box.
+    pub sah_cost: f64,
+}
+
+impl BvhStats {
+    pub fn average_leaf_size(&self) -> f64 {
+        if self.leaf_count == 0 {
+            return 0.0;
+        }
+
+        self.primitive_count as f64 / self.leaf_count as f64
+    }
+
+    fn record_leaf(&mut self, size: usize, depth: usize) {
+        self.leaf_count += 1;
+        self.node_count += 1;
+        self.max_depth = self.max_depth.max(depth);
+        self.smallest_leaf = if self.leaf_count == 1 {
+            size
+        } else {
+            self.smallest_leaf.min(size)
+        };
+        self.largest_leaf = self.largest_leaf.max(size);
+    }
+}
+
+impl BvhBuilder {
+    pub fn new(split_method: SplitMethod) -> Self {
+        let max_leaf_size = match split_method {
+            SplitMethod::Sah { .. } => 4,
+            SplitMethod::EqualCounts | SplitMethod::Middle => 1,
+        };
+
+        Self {
+            split_method,
+            max_leaf_size,
+            traversal_cost: 1.0,
+            intersection_cost: 1.0,
+        }
+    }
+
+    pub fn max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.max(1);
+        self
+    }
+
+    /// Builds a hierarchy over every object of `list` and reports what the tree looks like.
+    ///
+    /// Panics if the list is empty or if one of its objects has no bounding box.
+    pub fn build(&self, list: &HittableList) -> (BvhNode, BvhStats) {
+        assert!(
+            !list.objects.is_empty(),
+            "Cannot build a BVH from an empty list"
+        );
+
+        let bounds: Vec<Aabb> = list
+            .objects
+            .iter()
+            .map(|object| {
+                object
+                    .bounding_box()
+                    .expect("No bounding box in BvhNode constructor")
+            })
+            .collect();
+        let mut indices: Vec<usize> = (0..bounds.len()).collect();
+
+        let mut stats = BvhStats {
+            primitive_count: bounds.len(),
+            ..Default::default()
+        };
+
+        let root = if indices.len() == 1 {
+            stats.record_leaf(1, 1);
+            stats.node_count += 1;
+            stats.interior_count += 1;
+            stats.sah_cost = self.traversal_cost + self.intersection_cost;
+
+            BvhNode {
+                left: list.objects[0].clone(),
+                right: list.objects[0].clone(),
+                bbox: bounds[0],
+            }
+        } else {
+            let root_area = union_of(&bounds, &indices).surface_area();
+            let mut build = Build {
+                builder: self,
+                objects: &list.objects,
+                bounds: &bounds,
+                root_area,
+                stats: &mut stats,
+            };
+
+            // Always split the root, even if it would fit in a leaf, so it is a real node.
+            let mid = self
+                .partition(&bounds, &mut indices)
+                .unwrap_or(indices.len() / 2);
+
+            build.interior(&mut indices, mid, 0)
+        };
+
+        (root, stats)
+    }
+
+    /// Reorders `indices` so the primitives of the first child come first and returns
+    /// how many of them there are. Returns `None` when the range is better left as a leaf.
+    pub(crate) fn partition(&self, bounds: &[Aabb], indices: &mut [usize]) -> Option<usize> {
+        let len = indices.len();
+
+        if len <= 1 {
+            return None;
+        }
+
+        let centroid_bounds = indices
+            .iter()
+            .map(|&i| Aabb::new(bounds[i].centroid(), bounds[i].centroid()))
+            .reduce(Aabb::surrounding_box)?;
+        let axis = centroid_bounds.longest_axis();
+        let min = centroid_bounds.minimum[axis];
+        let extent = centroid_bounds.maximum[axis] - min;
+
+        if extent <= 0.0 {
+            // Every centroid coincides, no plane can separate them.
+            return if len > self.max_leaf_size {
+                Some(len / 2)
+            } else {
+                None
+            };
+        }
+
+        let median_split = |indices: &mut [usize]| {
+            indices.select_nth_unstable_by(len / 2, |&a, &b| {
+                let a = bounds[a].centroid()[axis];
+                let b = bounds[b].centroid()[axis];
+                a.total_cmp(&b)
+            });
+            len / 2
+        };
+
+        let mid = match self.split_method {
+            SplitMethod::Middle => {
+                if len <= self.max_leaf_size {
+                    return None;
+                }
+
+                let pivot = min + 0.5 * extent;
+                partition_in_place(indices, |&i| bounds[i].centroid()[axis] < pivot)
+            }
+            SplitMethod::EqualCounts => {
+                if len <= self.max_leaf_size {
+                    return None;
+                }
+
+                return Some(median_split(indices));
+            }
+            SplitMethod::Sah { bins } => {
+                let bins = bins.max(2);
+                let bin_of = |i: usize| {
+                    let offset = (bounds[i].centroid()[axis] - min) / extent;
+                    ((offset * bins as f64) as usize).min(bins - 1)
+                };
+
+                let mut counts = vec![0usize; bins];
+                let mut bin_bounds: Vec<Option<Aabb>> = vec![None; bins];
+                for &i in indices.iter() {
+                    let b = bin_of(i);
+                    counts[b] += 1;
+                    bin_bounds[b] = Some(match bin_bounds[b] {
+                        Some(bbox) => Aabb::surrounding_box(bbox, bounds[i]),
+                        None => bounds[i],
+                    });
+                }
+
+                let node_area = union_of(bounds, indices).surface_area();
+                let (best_split, best_cost) = (1..bins)
+                    .map(|split| {
+                        let cost = self.traversal_cost
+                            + self.intersection_cost
+                                * (side_cost(&counts[..split], &bin_bounds[..split])
+                                    + side_cost(&counts[split..], &bin_bounds[split..]))
+                                / node_area;
+                        (split, cost)
+                    })
+                    .fold((0, f64::INFINITY), |best, candidate| {
+                        if candidate.1 < best.1 {
+                            candidate
+                        } else {
+                            best
+                        }
+                    });
+
+                let leaf_cost = self.intersection_cost * len as f64;
+                if len <= self.max_leaf_size && leaf_cost <= best_cost {
+                    return None;
+                }
+
+                partition_in_place(indices, |&i| bin_of(i) < best_split)
+            }
+        };
+
+        if mid == 0 || mid == len {
+            // The chosen plane put everything on one side, fall back to a median split.
+            return Some(median_split(indices));
+        }
+
+        Some(mid)
+    }
+}
+
+struct Build<'a> {
+    builder: &'a BvhBuilder,
+    objects: &'a [Arc<dyn Hittable + Send + Sync>],
+    bounds: &'a [Aabb],
+    root_area: f64,
+    stats: &'a mut BvhStats,
+}
+
+impl Build<'_> {
+    fn node(&mut self, indices: &mut [usize], depth: usize) -> Arc<dyn Hittable + Send + Sync> {
+        match self.builder.partition(self.bounds, indices) {
+            Some(mid) => Arc::new(self.interior(indices, mid, depth)),
+            None => self.leaf(indices, depth),
+        }
+    }
+
+    fn interior(&mut self, indices: &mut [usize], mid: usize, depth: usize) -> BvhNode {
+        let bbox = union_of(self.bounds, indices);
+
+        self.stats.node_count += 1;
+        self.stats.interior_count += 1;
+        self.stats.sah_cost += self.builder.traversal_cost * bbox.surface_area() / self.root_area;
+
+        let (lower, upper) = indices.split_at_mut(mid);
+        let left = self.node(lower, depth + 1);
+        let right = self.node(upper, depth + 1);
+
+        BvhNode { left, right, bbox }
+    }
+
+    fn leaf(&mut self, indices: &[usize], depth: usize) -> Arc<dyn Hittable + Send + Sync> {
+        let bbox = union_of(self.bounds, indices);
+
+        self.stats.record_leaf(indices.len(), depth);
+        self.stats.sah_cost +=
+            self.builder.intersection_cost * indices.len() as f64 * bbox.surface_area()
+                / self.root_area;
+
+        if let [index] = indices {
+            return self.objects[*index].clone();
+        }
+
+        let mut list = HittableList::new();
+        for &i in indices {
+            list.add(self.objects[i].clone());
+        }
+
+        Arc::new(list)
+    }
+}
+
+fn union_of(bounds: &[Aabb], indices: &[usize]) -> Aabb {
+    indices
+        .iter()
+        .map(|&i| bounds[i])
+        .reduce(Aabb::surrounding_box)
+        .expect("Cannot compute the bounds of an empty range")
+}
+
+fn side_cost(counts: &[usize], bounds: &[Option<Aabb>]) -> f64 {
+    let count: usize = counts.iter().sum();
+    let area = bounds
+        .iter()
+        .flatten()
+        .copied()
+        .reduce(Aabb::surrounding_box)
+        .map_or(0.0, |bbox| bbox.surface_area());
+
+    count as f64 * area
+}
+
+/// Moves the elements matching `pred` to the front and returns how many there are.
+fn partition_in_place<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+    let mut first = 0;
+
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(first, i);
+            first += 1;
+        }
+    }
+
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::sphere::Sphere;
+    use crate::vec3::{Color, Point3, Vec3};
+
+    use rand::prelude::*;
+
+    fn grid_of_spheres() -> HittableList {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+
+        for i in 0..10 {
+            for j in 0..10 {
+                let center = Point3::new(i as f64 - 5.0, j as f64 - 5.0, (i * j) as f64 * 0.1);
+                list.add(Arc::new(Sphere::new(center, 0.3, material.clone())));
+            }
+        }
+
+        list
+    }
+
+    #[test]
+    fn bvh_matches_linear_list() {
+        let list = grid_of_spheres();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for split_method in [
+            SplitMethod::Sah { bins: 12 },
+            SplitMethod::EqualCounts,
+            SplitMethod::Middle,
+        ] {
+            let (bvh, stats) = BvhBuilder::new(split_method).build(&list);
+
+            assert_eq!(stats.primitive_count, 100);
+            assert_eq!(stats.node_count, stats.interior_count + stats.leaf_count);
+
+            for _ in 0..500 {
+                let origin = Point3::new(rng.gen_range(-6.0..6.0), rng.gen_range(-6.0..6.0), -10.0);
+                let direction = Vec3::new(rng.gen_range(-0.2..0.2), rng.gen_range(-0.2..0.2), 1.0);
+                let r = Ray::new(origin, direction);
+
+                let list_hit = list.hit(r, 0.001, f64::INFINITY);
+                let bvh_hit = bvh.hit(r, 0.001, f64::INFINITY);
+
+                assert_eq!(list_hit.is_some(), bvh_hit.is_some());
+                if let (Some(list_rec), Some(bvh_rec)) = (list_hit, bvh_hit) {
+                    assert_eq!(list_rec.t, bvh_rec.t);
+                    assert_eq!(list_rec.p, bvh_rec.p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_hits_hollow_spheres() {
+        // Negative radii flip the normals, not the bounding boxes.
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for x in [-3.0, 3.0] {
+            let center = Point3::new(x, 0.0, 0.0);
+            list.add(Arc::new(Sphere::new(center, -0.8, material.clone())));
+        }
+        let (bvh, _) = BvhBuilder::new(SplitMethod::Sah { bins: 12 }).build(&list);
+
+        for x in [-3.0, 3.0] {
+            let r = Ray::new(Point3::new(x, 0.0, -10.0), Vec3::new(0.0, 0.0, 1.0));
+            let list_rec = list.hit(r, 0.001, f64::INFINITY).unwrap();
+            let bvh_rec = bvh.hit(r, 0.001, f64::INFINITY).unwrap();
+
+            assert_eq!(list_rec.t, 9.2);
+            assert_eq!(bvh_rec.t, list_rec.t);
+            assert_eq!(bvh_rec.p, list_rec.p);
+        }
+    }
+
+    #[test]
+    fn bvh_lights_are_sampled() {
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        let lamp = |y: f64| Arc::new(Sphere::new(Point3::new(0.0, y, 0.0), 1.0, light.clone()));
+
+        // One lamp among the spheres of a BVH in a nested list, another at the top.
+        let mut spheres = grid_of_spheres();
+        spheres.add(lamp(10.0));
+        let mut nested = HittableList::new();
+        nested.add(Arc::new(BvhNode::new(&spheres)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(nested));
+        world.add(lamp(20.0));
+
+        let lights = world.lights();
+        assert_eq!(lights.objects.len(), 2);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        assert!(lights.pdf_value(origin, Vec3::new(0.0, 1.0, 0.0)) > 0.0);
+        assert_eq!(lights.pdf_value(origin, Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn bvh_leaf_sizes_respect_builder() {
+        let list = grid_of_spheres();
+
+        let (_, stats) = BvhBuilder::new(SplitMethod::EqualCounts).build(&list);
+        assert_eq!(stats.leaf_count, 100);
+        assert_eq!(stats.largest_leaf, 1);
+        assert_eq!(stats.max_depth, 7);
+
+        let (_, stats) = BvhBuilder::new(SplitMethod::Sah { bins: 16 })
+            .max_leaf_size(8)
+            .build(&list);
+        assert!(stats.largest_leaf <= 8);
+        assert_eq!(stats.average_leaf_size() * stats.leaf_count as f64, 100.0);
+    }
+
+    #[test]
+    fn bvh_sah_is_not_worse_than_middle() {
+        let list = grid_of_spheres();
+
+        let (_, sah) = BvhBuilder::new(SplitMethod::Sah { bins: 32 })
+            .max_leaf_size(1)
+            .build(&list);
+        let (_, middle) = BvhBuilder::new(SplitMethod::Middle).build(&list);
+
+        assert!(sah.sah_cost <= middle.sah_cost);
+    }
+}
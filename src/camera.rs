@@ -1,6 +1,8 @@
 use crate::vec3::*;
 use crate::ray::Ray;
 
+use rand::Rng;
+
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
@@ -8,9 +10,9 @@ pub struct Camera {
     horizontal: Vec3,
     vertical: Vec3,
     lower_left_corner: Point3,
-    w: Vec3,
     u: Vec3,
     v: Vec3,
+    w: Vec3,
     lens_radius: f64
 }
 
@@ -39,19 +41,62 @@ impl Camera {
             horizontal,
             vertical,
             lower_left_corner,
-            w,
             u,
             v,
+            w,
             lens_radius
         }
     }
 }
 
+/// The placement and lens of a camera, everything `Camera::new` needs but the aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+        )
+    }
+}
+
 impl Camera {
-    pub fn get_ray(self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    pub fn get_ray<R: Rng + ?Sized>(self, s: f64, t: f64, rng: &mut R) -> Ray {
+        let rd = self.lens_radius * Vec3::random_in_unit_disk(rng);
         let offset = self.u * rd.x + self.v * rd.y;
 
         Ray::new(self.origin + offset, self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset)
     }
+
+    /// Distance from the camera to `p` along the viewing direction.
+    pub fn depth(&self, p: Point3) -> f64 {
+        (p - self.origin).dot(-self.w)
+    }
 }
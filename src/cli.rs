@@ -0,0 +1,666 @@
+use ray_tracing::adaptive::AdaptiveSampling;
+use ray_tracing::aov::Aov;
+use ray_tracing::denoise::Denoiser;
+use ray_tracing::integrator::Integrator;
+use ray_tracing::output::OutputFormat;
+use ray_tracing::scene::{self, Scene};
+use ray_tracing::scenes::BUILTIN_SCENES;
+use ray_tracing::tonemap::{ToneMapper, ToneMapping};
+use ray_tracing::vec3::Vec3;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: ray_tracing [OPTIONS]
+
+Renders a scene to an image.
+
+Options:
+  -s, --scene <SCENE>          Built-in scene name or path to a JSON scene file [default: random]
+  -o, --output <PATH>          Output image [default: output.png]
+  -f, --format <FORMAT>        Output format: png, jpeg, bmp, tga, tiff, ppm, ..., or exr,
+                               hdr and pfm to keep the linear radiance
+                               [default: guessed from the output extension]
+      --tone-map <NAME>        Tone mapping of 8 bit outputs: clamp, reinhard,
+                               reinhard-extended, aces, hable or agx [default: clamp]
+      --exposure <STOPS>       Exposure adjustment of 8 bit outputs, in stops [default: 0]
+      --white-point <VALUE>    Luminance mapped to white by reinhard-extended [default: 4]
+      --aov <NAMES>            Also write these comma separated passes: albedo, normal,
+                               position, depth, object_id, material_id, samples, or all.
+                               OpenEXR outputs get them as extra channels, other formats
+                               as images named like output.albedo.png
+  -w, --width <PIXELS>         Image width
+      --height <PIXELS>        Image height
+      --aspect-ratio <RATIO>   Image aspect ratio, as a number or W:H, used with --width
+  -n, --samples <N>            Samples per pixel
+  -d, --max-depth <N>          Maximum number of bounces per path
+  -j, --threads <N>            Number of worker threads [default: available cores]
+      --integrator <NAME>      Light transport: bsdf, nee (next-event estimation) or mis
+                               (multiple importance sampling) [default: mis]
+      --seed <N>               Seed of the random number generators [default: 0]
+      --progressive <N>        Render in passes of N samples per pixel and write the image
+                               after each pass
+      --snapshot-interval <SECONDS>
+                               Write the image at most every SECONDS while rendering, in
+                               passes of 1 sample per pixel unless --progressive is given
+  -q, --quiet                  Do not show the progress bar
+
+Adaptive sampling:
+      --adaptive               Stop sampling the pixels that have converged, each taking
+                               at most --samples
+      --adaptive-threshold <ERROR>
+                               Noise left in converged pixels, relative to their
+                               brightness [default: 0.02]
+      --adaptive-min-samples <N>
+                               Samples every pixel takes before it can converge
+                               [default: 16]
+
+Denoising:
+      --denoise                Filter out the noise, guided by the albedo and normals of
+                               the image
+      --denoise-iterations <N> Passes of the filter, each reaching twice as far [default: 5]
+      --denoise-color <SIGMA>  Color difference the filter averages over [default: 0.5]
+      --denoise-normal <SIGMA> Normal difference the filter averages over [default: 0.3]
+      --denoise-albedo <SIGMA> Albedo difference the filter averages over [default: 0.1]
+
+Camera overrides:
+      --lookfrom <X,Y,Z>       Camera position
+      --lookat <X,Y,Z>         Point the camera looks at
+      --vup <X,Y,Z>            Up direction
+      --vfov <DEGREES>         Vertical field of view
+      --aperture <SIZE>        Lens aperture, 0 for a pinhole camera
+      --focus-dist <DISTANCE>  Distance to the plane in focus
+
+  -h, --help                   Print this help
+
+Scene settings not given on the command line come from the scene. Interrupting a render
+with Ctrl-C writes the part of the image done so far.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Render(Box<Options>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub output: Option<PathBuf>,
+    pub format: Option<OutputFormat>,
+    pub tone_map: Option<ToneMapper>,
+    pub exposure: Option<f64>,
+    pub white_point: Option<f64>,
+    pub aovs: Vec<Aov>,
+    pub adaptive: bool,
+    pub adaptive_threshold: Option<f64>,
+    pub adaptive_min_samples: Option<u32>,
+    pub denoise: bool,
+    pub denoise_iterations: Option<u32>,
+    pub denoise_color: Option<f64>,
+    pub denoise_normal: Option<f64>,
+    pub denoise_albedo: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples: Option<u32>,
+    pub max_depth: Option<i32>,
+    pub threads: Option<usize>,
+    pub integrator: Option<Integrator>,
+    pub seed: Option<u64>,
+    pub quiet: bool,
+    pub progressive: Option<u32>,
+    pub snapshot_interval: Option<Duration>,
+    pub lookfrom: Option<Vec3>,
+    pub lookat: Option<Vec3>,
+    pub vup: Option<Vec3>,
+    pub vfov: Option<f64>,
+    pub aperture: Option<f64>,
+    pub focus_dist: Option<f64>,
+}
+
+/// Parses the arguments following the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // Accept both `--name value` and `--name=value`.
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+
+        if name == "-h" || name == "--help" {
+            return Ok(Command::Help);
+        }
+        if name == "-q" || name == "--quiet" {
+            options.quiet = true;
+            continue;
+        }
+        if name == "--adaptive" {
+            options.adaptive = true;
+            continue;
+        }
+        if name == "--denoise" {
+            options.denoise = true;
+            continue;
+        }
+
+        let mut value = || match inline_value {
+            Some(value) => Ok(value.to_string()),
+            None => args
+                .next()
+                .ok_or_else(|| format!("'{}' requires a value", name)),
+        };
+
+        match name.as_str() {
+            "-s" | "--scene" => options.scene = Some(value()?),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let value = value()?;
+                options.format = Some(
+                    OutputFormat::from_extension(&value)
+                        .ok_or_else(|| format!("unknown image format '{}'", value))?,
+                );
+            }
+            "--tone-map" => {
+                let value = value()?;
+                options.tone_map = Some(ToneMapper::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown tone mapper '{}', expected one of: {}",
+                        value,
+                        ToneMapper::NAMES.join(", ")
+                    )
+                })?);
+            }
+            "--exposure" => options.exposure = Some(finite(&name, &value()?)?),
+            "--white-point" => options.white_point = Some(positive_float(&name, &value()?)?),
+            "--aov" => options.aovs.extend(aovs(&value()?)?),
+            "--adaptive-threshold" => {
+                options.adaptive_threshold = Some(positive_float(&name, &value()?)?)
+            }
+            "--adaptive-min-samples" => {
+                options.adaptive_min_samples = Some(positive(&name, &value()?)?)
+            }
+            "--denoise-iterations" => {
+                options.denoise_iterations = Some(positive(&name, &value()?)?)
+            }
+            "--denoise-color" => options.denoise_color = Some(positive_float(&name, &value()?)?),
+            "--denoise-normal" => options.denoise_normal = Some(positive_float(&name, &value()?)?),
+            "--denoise-albedo" => options.denoise_albedo = Some(positive_float(&name, &value()?)?),
+            "-w" | "--width" => options.width = Some(positive(&name, &value()?)?),
+            "--height" => options.height = Some(positive(&name, &value()?)?),
+            "--aspect-ratio" => options.aspect_ratio = Some(aspect_ratio(&value()?)?),
+            "-n" | "--samples" => options.samples = Some(positive(&name, &value()?)?),
+            "-d" | "--max-depth" => options.max_depth = Some(positive(&name, &value()?)?),
+            "-j" | "--threads" => options.threads = Some(positive(&name, &value()?)?),
+            "--integrator" => {
+                let value = value()?;
+                options.integrator = Some(Integrator::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown integrator '{}', expected one of: {}",
+                        value,
+                        Integrator::NAMES.join(", ")
+                    )
+                })?);
+            }
+            "--seed" => options.seed = Some(number(&name, &value()?)?),
+            "--progressive" => options.progressive = Some(positive(&name, &value()?)?),
+            "--snapshot-interval" => {
+                let value = value()?;
+                let seconds = positive_float(&name, &value)?;
+                options.snapshot_interval = Some(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| format!("'{}' is out of range, got '{}'", name, value))?,
+                );
+            }
+            "--lookfrom" => options.lookfrom = Some(vector(&name, &value()?)?),
+            "--lookat" => options.lookat = Some(vector(&name, &value()?)?),
+            "--vup" => options.vup = Some(vector(&name, &value()?)?),
+            "--vfov" => options.vfov = Some(finite(&name, &value()?)?),
+            "--aperture" => options.aperture = Some(finite(&name, &value()?)?),
+            "--focus-dist" => options.focus_dist = Some(finite(&name, &value()?)?),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    options.aovs.sort();
+    options.aovs.dedup();
+
+    if options.height.is_some() && options.aspect_ratio.is_some() {
+        return Err("'--height' cannot be used with '--aspect-ratio'".to_string());
+    }
+    if !options.adaptive
+        && (options.adaptive_threshold.is_some() || options.adaptive_min_samples.is_some())
+    {
+        return Err("the '--adaptive-*' options require '--adaptive'".to_string());
+    }
+    let denoise_settings = [
+        options.denoise_color,
+        options.denoise_normal,
+        options.denoise_albedo,
+    ];
+    if !options.denoise
+        && (options.denoise_iterations.is_some() || denoise_settings.iter().any(Option::is_some))
+    {
+        return Err("the '--denoise-*' options require '--denoise'".to_string());
+    }
+
+    Ok(Command::Render(Box::new(options)))
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name))
+}
+
+fn positive<T: std::str::FromStr + PartialOrd + Default>(
+    name: &str,
+    value: &str,
+) -> Result<T, String> {
+    let number: T = number(name, value)?;
+
+    if number <= T::default() {
+        return Err(format!("'{}' must be positive, got '{}'", name, value));
+    }
+
+    Ok(number)
+}
+
+fn finite(name: &str, value: &str) -> Result<f64, String> {
+    let number: f64 = number(name, value)?;
+
+    if !number.is_finite() {
+        return Err(format!(
+            "'{}' must be a finite number, got '{}'",
+            name, value
+        ));
+    }
+
+    Ok(number)
+}
+
+fn positive_float(name: &str, value: &str) -> Result<f64, String> {
+    let number = finite(name, value)?;
+
+    if number <= 0.0 {
+        return Err(format!("'{}' must be positive, got '{}'", name, value));
+    }
+
+    Ok(number)
+}
+
+fn aspect_ratio(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(':') {
+        Some((width, height)) => {
+            number::<f64>("--aspect-ratio", width)? / number::<f64>("--aspect-ratio", height)?
+        }
+        None => number("--aspect-ratio", value)?,
+    };
+
+    if !(ratio > 0.0 && ratio.is_finite()) {
+        return Err(format!("invalid value '{}' for '--aspect-ratio'", value));
+    }
+
+    Ok(ratio)
+}
+
+fn aovs(value: &str) -> Result<Vec<Aov>, String> {
+    if value == "all" {
+        return Ok(Aov::ALL.to_vec());
+    }
+
+    value
+        .split(',')
+        .map(|name| {
+            Aov::from_name(name.trim()).ok_or_else(|| {
+                format!(
+                    "unknown AOV '{}', expected all or some of: {}",
+                    name,
+                    Aov::NAMES.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn vector(name: &str, value: &str) -> Result<Vec3, String> {
+    let components = value
+        .split(',')
+        .map(|c| finite(name, c.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!(
+            "'{}' expects three comma separated numbers, got '{}'",
+            name, value
+        )),
+    }
+}
+
+impl Options {
+    pub fn seed(&self) -> u64 {
+        self.seed.unwrap_or(0)
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(4, |threads| threads.get())
+        })
+    }
+
+    /// The samples per pixel of every pass, `None` to render in a single pass. Adaptive
+    /// renders check which pixels have converged after every pass of the minimum samples.
+    pub fn pass_samples(&self) -> Option<u32> {
+        self.progressive
+            .or(self.snapshot_interval.map(|_| 1))
+            .or(self
+                .adaptive_sampling()
+                .map(|adaptive| adaptive.min_samples))
+    }
+
+    /// The minimum time between two snapshots of a progressive render.
+    pub fn snapshot_interval(&self) -> Duration {
+        self.snapshot_interval.unwrap_or(Duration::ZERO)
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        let default = ToneMapping::default();
+
+        ToneMapping {
+            operator: self.tone_map.unwrap_or_default(),
+            exposure: self.exposure.unwrap_or(default.exposure),
+            white_point: self.white_point.unwrap_or(default.white_point),
+        }
+    }
+
+    /// When pixels stop taking samples, if they ever do before the samples per pixel.
+    pub fn adaptive_sampling(&self) -> Option<AdaptiveSampling> {
+        let default = AdaptiveSampling::default();
+
+        self.adaptive.then(|| AdaptiveSampling {
+            min_samples: self.adaptive_min_samples.unwrap_or(default.min_samples),
+            threshold: self.adaptive_threshold.unwrap_or(default.threshold),
+        })
+    }
+
+    /// The denoiser to run on the image, if any.
+    pub fn denoiser(&self) -> Option<Denoiser> {
+        let default = Denoiser::default();
+
+        self.denoise.then(|| Denoiser {
+            iterations: self.denoise_iterations.unwrap_or(default.iterations),
+            color_sigma: self.denoise_color.unwrap_or(default.color_sigma),
+            normal_sigma: self.denoise_normal.unwrap_or(default.normal_sigma),
+            albedo_sigma: self.denoise_albedo.unwrap_or(default.albedo_sigma),
+        })
+    }
+
+    pub fn output(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("output.png"))
+    }
+
+    pub fn output_format(&self) -> Result<OutputFormat, String> {
+        let output = self.output();
+
+        match self.format {
+            Some(format) => Ok(format),
+            None => OutputFormat::from_path(&output).ok_or_else(|| {
+                format!(
+                    "cannot guess the image format of '{}', use '--format'",
+                    output.display()
+                )
+            }),
+        }
+    }
+
+    /// Loads the scene file or builds the built-in scene, then applies the overrides.
+    pub fn load_scene(&self) -> Result<Scene, String> {
+        let name = self.scene.as_deref().unwrap_or("random");
+
+        let mut scene = match ray_tracing::scenes::builtin(name, self.seed()) {
+            Some(scene) => scene,
+            None if name.ends_with(".json") || PathBuf::from(name).exists() => {
+                Scene::load(name).map_err(|error| error.to_string())?
+            }
+            None => {
+                return Err(format!(
+                    "unknown scene '{}', expected a scene file or one of: {}",
+                    name,
+                    BUILTIN_SCENES.join(", ")
+                ))
+            }
+        };
+
+        self.apply(&mut scene)?;
+
+        Ok(scene)
+    }
+
+    fn apply(&self, scene: &mut Scene) -> Result<(), String> {
+        let settings = &mut scene.settings;
+
+        if self.width.is_some() || self.height.is_some() || self.aspect_ratio.is_some() {
+            settings.set_image_size(
+                self.width.unwrap_or(settings.image_width),
+                self.height,
+                self.aspect_ratio,
+            )?;
+        }
+        settings.samples_per_pixel = self.samples.unwrap_or(settings.samples_per_pixel);
+        settings.max_depth = self.max_depth.unwrap_or(settings.max_depth);
+
+        let camera = &mut scene.camera;
+        camera.lookfrom = self.lookfrom.unwrap_or(camera.lookfrom);
+        camera.lookat = self.lookat.unwrap_or(camera.lookat);
+        camera.vup = self.vup.unwrap_or(camera.vup);
+        camera.vfov = self.vfov.unwrap_or(camera.vfov);
+        camera.aperture = self.aperture.unwrap_or(camera.aperture);
+        camera.focus_dist = self.focus_dist.unwrap_or(camera.focus_dist);
+
+        scene::validate_settings(&scene.settings)?;
+        scene::validate_camera(&scene.camera)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::ImageFormat;
+
+    fn options(args: &[&str]) -> Options {
+        match parse(args.iter().map(|arg| arg.to_string())) {
+            Ok(Command::Render(options)) => *options,
+            other => panic!("expected render options, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cli_parses_options() {
+        let options = options(&[
+            "--scene",
+            "scenes/three_spheres.json",
+            "-o",
+            "render.jpg",
+            "--width=800",
+            "--aspect-ratio",
+            "16:9",
+            "-n",
+            "64",
+            "--lookfrom",
+            "1, 2,3",
+            "--seed",
+            "42",
+            "--integrator",
+            "nee",
+            "-q",
+            "--snapshot-interval",
+            "2.5",
+            "--tone-map",
+            "agx",
+            "--exposure=-1.5",
+            "--aov",
+            "normal,albedo",
+            "--aov=albedo",
+            "--denoise",
+            "--denoise-iterations",
+            "3",
+            "--adaptive",
+            "--adaptive-threshold=0.05",
+        ]);
+
+        assert_eq!(options.scene.as_deref(), Some("scenes/three_spheres.json"));
+        assert_eq!(
+            options.output_format(),
+            Ok(OutputFormat::Ldr(ImageFormat::Jpeg))
+        );
+        assert_eq!(options.width, Some(800));
+        assert_eq!(options.aspect_ratio, Some(16.0 / 9.0));
+        assert_eq!(options.samples, Some(64));
+        assert_eq!(options.lookfrom, Some(Vec3::new(1.0, 2.0, 3.0)));
+        assert_eq!(options.seed(), 42);
+        assert_eq!(options.integrator, Some(Integrator::NextEvent));
+        assert!(options.quiet);
+        assert_eq!(options.pass_samples(), Some(1));
+        assert_eq!(options.snapshot_interval(), Duration::from_millis(2500));
+        assert_eq!(options.aovs, [Aov::Albedo, Aov::Normal]);
+        assert_eq!(
+            options.denoiser(),
+            Some(Denoiser {
+                iterations: 3,
+                ..Denoiser::default()
+            })
+        );
+        assert_eq!(
+            options.adaptive_sampling(),
+            Some(AdaptiveSampling {
+                threshold: 0.05,
+                ..AdaptiveSampling::default()
+            })
+        );
+        assert_eq!(
+            options.tone_mapping(),
+            ToneMapping {
+                operator: ToneMapper::Agx,
+                exposure: -1.5,
+                ..ToneMapping::default()
+            }
+        );
+
+        assert_eq!(
+            parse(["-w", "10", "--help"].map(String::from)),
+            Ok(Command::Help)
+        );
+    }
+
+    #[test]
+    fn cli_rejects_invalid_values() {
+        assert_eq!(
+            parse(["--width", "wide"].map(String::from)),
+            Err("invalid value 'wide' for '--width'".to_string())
+        );
+        assert_eq!(
+            parse(["--samples", "0"].map(String::from)),
+            Err("'--samples' must be positive, got '0'".to_string())
+        );
+        assert_eq!(
+            parse(["--threads"].map(String::from)),
+            Err("'--threads' requires a value".to_string())
+        );
+        assert_eq!(
+            parse(["--lookat", "1,2"].map(String::from)),
+            Err("'--lookat' expects three comma separated numbers, got '1,2'".to_string())
+        );
+        for (flag, value) in [
+            ("--vfov", "inf"),
+            ("--aperture", "NaN"),
+            ("--snapshot-interval", "NaN"),
+            ("--exposure", "NaN"),
+            ("--white-point", "inf"),
+            ("--denoise-color", "NaN"),
+            ("--adaptive-threshold", "inf"),
+        ] {
+            assert_eq!(
+                parse([flag, value].map(String::from)),
+                Err(format!(
+                    "'{}' must be a finite number, got '{}'",
+                    flag, value
+                ))
+            );
+        }
+        assert_eq!(
+            parse(["--lookfrom", "1,NaN,3"].map(String::from)),
+            Err("'--lookfrom' must be a finite number, got 'NaN'".to_string())
+        );
+        assert_eq!(
+            parse(["--snapshot-interval", "1e30"].map(String::from)),
+            Err("'--snapshot-interval' is out of range, got '1e30'".to_string())
+        );
+        assert_eq!(
+            parse(["--frobnicate"].map(String::from)),
+            Err("unexpected argument '--frobnicate'".to_string())
+        );
+        assert!(parse(["--height", "10", "--aspect-ratio", "2"].map(String::from)).is_err());
+        assert!(options(&["-o", "render"]).output_format().is_err());
+        assert_eq!(
+            parse(["--denoise-color", "0.2"].map(String::from)),
+            Err("the '--denoise-*' options require '--denoise'".to_string())
+        );
+        assert_eq!(options(&[]).denoiser(), None);
+        assert_eq!(
+            parse(["--adaptive-min-samples", "8"].map(String::from)),
+            Err("the '--adaptive-*' options require '--adaptive'".to_string())
+        );
+        assert_eq!(options(&[]).adaptive_sampling(), None);
+        assert_eq!(
+            parse(["--aov", "albedo,beauty"].map(String::from)),
+            Err(
+                "unknown AOV 'beauty', expected all or some of: albedo, normal, position, \
+                 depth, object_id, material_id, samples"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse(["--tone-map", "filmic"].map(String::from)),
+            Err(
+                "unknown tone mapper 'filmic', expected one of: clamp, reinhard, \
+                 reinhard-extended, aces, hable, agx"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            options(&["-o", "render.pfm"]).output_format(),
+            Ok(OutputFormat::Pfm)
+        );
+        assert_eq!(
+            options(&["-o", "render", "-f", "exr"]).output_format(),
+            Ok(OutputFormat::OpenExr)
+        );
+    }
+
+    #[test]
+    fn cli_overrides_scene_settings() {
+        let scene = options(&["--width", "200", "--vfov", "30", "--aperture", "0"])
+            .load_scene()
+            .unwrap();
+
+        assert_eq!(scene.settings.image_width, 200);
+        assert_eq!(scene.settings.image_height, 133);
+        assert_eq!(scene.camera.vfov, 30.0);
+        assert_eq!(scene.camera.aperture, 0.0);
+
+        let error = options(&["--vfov", "200"]).load_scene().err().unwrap();
+        assert!(error.contains("vfov"), "{}", error);
+
+        let error = options(&["--scene", "nowhere"]).load_scene().err().unwrap();
+        assert!(error.contains("unknown scene 'nowhere'"), "{}", error);
+    }
+}
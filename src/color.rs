@@ -13,12 +13,30 @@ where
     x
 }
 
+/// Relative luminance of a linear Rec. 709 color.
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// The sRGB transfer function, from linear light in `[0, 1]` to the encoded value.
+pub fn linear_to_srgb(x: f64) -> f64 {
+    let x = clamp(x, 0.0, 1.0);
+
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Encodes the average of `samples_per_pixel` samples as 8 bit sRGB, clamping it to the
+/// displayable range.
 pub fn format_pixel_color(pixel_color: Color, samples_per_pixel: i32) -> (u8, u8, u8) {
     let scale = 1.0 / samples_per_pixel as f64;
 
-    let r = (pixel_color.x * scale).sqrt();
-    let g = (pixel_color.y * scale).sqrt();
-    let b = (pixel_color.z * scale).sqrt();
+    let r = linear_to_srgb(pixel_color.x * scale);
+    let g = linear_to_srgb(pixel_color.y * scale);
+    let b = linear_to_srgb(pixel_color.z * scale);
 
     (
         (256.0 * clamp(r, 0.0, 0.999)) as u8,
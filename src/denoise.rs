@@ -0,0 +1,226 @@
+use crate::color::luminance;
+use crate::renderer::Framebuffer;
+use crate::vec3::Color;
+
+use rayon::prelude::*;
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// The edge-avoiding À-trous wavelet transform of Dammertz et al., the spatial
+/// filter of SVGF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    pub iterations: u32,
+    pub color_sigma: f64,
+    pub normal_sigma: f64,
+    pub albedo_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 0.5,
+            normal_sigma: 0.3,
+            albedo_sigma: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    pub fn denoise(
+        &self,
+        color: &Framebuffer,
+        albedo: &Framebuffer,
+        normal: &Framebuffer,
+    ) -> Framebuffer {
+        let (width, height) = (color.width(), color.height());
+        assert!(
+            (albedo.width(), albedo.height()) == (width, height)
+                && (normal.width(), normal.height()) == (width, height),
+            "The AOVs must be the size of the image"
+        );
+
+        let mut pixels = color.pixels().to_vec();
+        for iteration in 0..self.iterations {
+            let image = Guided {
+                width: width as usize,
+                height: height as usize,
+                color: &pixels,
+                albedo: albedo.pixels(),
+                normal: normal.pixels(),
+            };
+            let step = 1 << iteration;
+            let color_sigma = self.color_sigma / step as f64;
+
+            let mut filtered = vec![Color::default(); pixels.len()];
+            filtered
+                .par_chunks_mut(width as usize)
+                .enumerate()
+                .for_each(|(y, row)| {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        *pixel = self.filter_pixel(&image, x, y, step, color_sigma);
+                    }
+                });
+
+            pixels = filtered;
+        }
+
+        let mut denoised = Framebuffer::new(width, height);
+        for (i, &pixel) in pixels.iter().enumerate() {
+            denoised.set(i as u32 % width, i as u32 / width, pixel);
+        }
+
+        denoised
+    }
+
+    fn filter_pixel(
+        &self,
+        image: &Guided,
+        x: usize,
+        y: usize,
+        step: usize,
+        color_sigma: f64,
+    ) -> Color {
+        let center = image.index(x, y);
+        let center_color = compress(image.color[center]);
+
+        let mut sum = Color::default();
+        let mut weights = 0.0;
+        for (j, ky) in KERNEL.iter().enumerate() {
+            let Some(ty) = offset(y, j, step, image.height) else {
+                continue;
+            };
+
+            for (i, kx) in KERNEL.iter().enumerate() {
+                let Some(tx) = offset(x, i, step, image.width) else {
+                    continue;
+                };
+
+                let tap = image.index(tx, ty);
+                let weight = kx
+                    * ky
+                    * edge_stop(center_color - compress(image.color[tap]), color_sigma)
+                    * edge_stop(image.normal[center] - image.normal[tap], self.normal_sigma)
+                    * edge_stop(image.albedo[center] - image.albedo[tap], self.albedo_sigma);
+
+                sum = sum + weight * image.color[tap];
+                weights += weight;
+            }
+        }
+
+        sum / weights
+    }
+}
+
+struct Guided<'a> {
+    width: usize,
+    height: usize,
+    color: &'a [Color],
+    albedo: &'a [Color],
+    normal: &'a [Color],
+}
+
+impl Guided<'_> {
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+}
+
+fn offset(center: usize, i: usize, step: usize, size: usize) -> Option<usize> {
+    let tap = center as isize + (i as isize - 2) * step as isize;
+
+    (0..size as isize).contains(&tap).then_some(tap as usize)
+}
+
+fn edge_stop(difference: Color, sigma: f64) -> f64 {
+    (-difference.length_squared() / (sigma * sigma)).exp()
+}
+
+fn compress(color: Color) -> Color {
+    color / (1.0 + luminance(color).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    fn framebuffer(
+        width: u32,
+        height: u32,
+        mut color: impl FnMut(u32, u32) -> Color,
+    ) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                framebuffer.set(x, y, color(x, y));
+            }
+        }
+
+        framebuffer
+    }
+
+    #[test]
+    fn removes_noise_but_keeps_edges() {
+        let (width, height) = (64, 48);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        // Two walls meeting at x = 32, a red and a white one, lit by the same light.
+        let left = |x: u32| x < 32;
+        let albedo = framebuffer(width, height, |x, _| {
+            if left(x) {
+                Color::new(0.8, 0.1, 0.1)
+            } else {
+                Color::new(0.8, 0.8, 0.8)
+            }
+        });
+        let normal = framebuffer(width, height, |x, _| {
+            if left(x) {
+                Color::new(1.0, 0.0, 0.0)
+            } else {
+                Color::new(0.0, 0.0, 1.0)
+            }
+        });
+        let clean = framebuffer(width, height, |x, y| 0.5 * albedo.get(x, y));
+        let noisy = framebuffer(width, height, |x, y| {
+            // Noise with the same mean, as with too few samples per pixel.
+            clean.get(x, y) * (2.0 * rng.gen::<f64>())
+        });
+
+        let denoised = Denoiser::default().denoise(&noisy, &albedo, &normal);
+
+        let error = |image: &Framebuffer| {
+            let squared: f64 = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| (image.get(x, y) - clean.get(x, y)).length_squared())
+                .sum();
+            (squared / (width * height) as f64).sqrt()
+        };
+        assert!(
+            error(&denoised) < 0.1 * error(&noisy),
+            "{} {}",
+            error(&denoised),
+            error(&noisy)
+        );
+
+        // Nothing of the red wall bleeds into the white one.
+        let next_to_edge = denoised.get(32, 20);
+        assert!((next_to_edge.x - next_to_edge.y).abs() < 0.02);
+    }
+
+    #[test]
+    fn leaves_clean_images_alone() {
+        let albedo = framebuffer(8, 8, |_, _| Color::new(0.5, 0.5, 0.5));
+        let normal = framebuffer(8, 8, |_, _| Color::new(0.0, 1.0, 0.0));
+        let color = Color::new(3.0, 2.0, 1.0);
+        let image = framebuffer(8, 8, |_, _| color);
+
+        let denoised = Denoiser::default().denoise(&image, &albedo, &normal);
+
+        for &pixel in denoised.pixels() {
+            assert!((pixel - color).length() < 1e-12);
+        }
+    }
+}
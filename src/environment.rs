@@ -0,0 +1,339 @@
+use crate::color::luminance;
+use crate::texture::direction_to_uv;
+use crate::vec3::{Color, Vec3};
+
+use image::codecs::hdr::HdrDecoder;
+use rand::Rng;
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// An equirectangular (latitude-longitude) image of the light arriving from every
+/// direction. The top row of the image looks straight up (+y) and the horizontal center
+/// looks towards +x, before rotation.
+///
+/// Directions can be importance sampled proportionally to the luminance of the map, so
+/// small bright features like the sun are found by a few samples.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    rotation: f64,
+    intensity: f64,
+    /// Distribution of the columns of every row, top row first.
+    rows: Vec<Distribution>,
+    /// Distribution of the rows, weighted by the total of every row.
+    marginal: Distribution,
+}
+
+/// A piecewise constant distribution over `[0, 1)`.
+#[derive(Debug, Clone)]
+struct Distribution {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution {
+    fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            // A distribution without weight falls back to uniform.
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// The density at segment `i`.
+    fn pdf(&self, i: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[i] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    /// Maps a uniform `u` to a position in `[0, 1)`, returning the position and the
+    /// segment it falls in.
+    fn sample(&self, u: f64) -> (f64, usize) {
+        let n = self.func.len();
+        let i = (self.cdf.partition_point(|&c| c <= u) - 1).min(n - 1);
+
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 {
+            ((u - self.cdf[i]) / width).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+
+        ((i as f64 + offset) / n as f64, i)
+    }
+}
+
+impl EnvironmentMap {
+    /// Panics if `pixels` does not hold `width * height` colors.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "Environment map without pixels");
+        assert_eq!(pixels.len(), width * height, "One color per pixel");
+
+        // Weight pixels by the solid angle they cover, which shrinks towards the poles.
+        let mut weights: Vec<f64> = (0..width * height)
+            .map(|i| luminance(pixels[i]).max(0.0) * row_sin_theta(i / width, height))
+            .collect();
+        if weights.iter().all(|&w| w == 0.0) {
+            weights = (0..width * height)
+                .map(|i| row_sin_theta(i / width, height))
+                .collect();
+        }
+
+        let rows: Vec<Distribution> = weights
+            .chunks(width)
+            .map(|row| Distribution::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution::new(rows.iter().map(|row| row.integral).collect());
+
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: 0.0,
+            intensity: 1.0,
+            rows,
+            marginal,
+        }
+    }
+
+    /// Rotates the map around the vertical axis, counterclockwise seen from above.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    /// Scales the radiance of the map.
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Loads a Radiance `.hdr` file, or any other format the `image` crate can decode
+    /// such as OpenEXR `.exr`. Colors are expected to be linear.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+
+        // Going through `image::open` would tone map Radiance files to 8 bits.
+        let (width, height, pixels) = if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder.read_image_hdr()?;
+
+            (metadata.width, metadata.height, pixels)
+        } else {
+            let image = image::open(path)?.into_rgb32f();
+
+            (
+                image.width(),
+                image.height(),
+                image.pixels().copied().collect(),
+            )
+        };
+
+        let pixels = pixels
+            .iter()
+            .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+
+        Ok(Self::new(width as usize, height as usize, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The radiance arriving from `direction`, which does not need to be normalized.
+    pub fn value(&self, direction: Vec3) -> Color {
+        let (x, y) = self.pixel(direction);
+
+        self.intensity * self.pixels[y * self.width + x]
+    }
+
+    /// Picks a direction proportionally to the luminance of the map, returning it with
+    /// its probability density per unit solid angle.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> (Vec3, f64) {
+        let (row_position, y) = self.marginal.sample(rng.gen());
+        let (u, x) = self.rows[y].sample(rng.gen());
+        let v = 1.0 - row_position;
+
+        let theta = PI * v;
+        let phi = 2.0 * PI * u;
+        let direction = Vec3::new(
+            -theta.sin() * phi.cos(),
+            -theta.cos(),
+            theta.sin() * phi.sin(),
+        );
+
+        (
+            rotate_y(direction, self.rotation),
+            self.pixel_pdf(x, y, theta.sin()),
+        )
+    }
+
+    /// The density [`sample`](Self::sample) picks `direction` with.
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        let (x, y) = self.pixel(direction);
+        let local = rotate_y(direction.unit_vector(), -self.rotation);
+        let sin_theta = (1.0 - local.y * local.y).max(0.0).sqrt();
+
+        self.pixel_pdf(x, y, sin_theta)
+    }
+
+    fn pixel_pdf(&self, x: usize, y: usize, sin_theta: f64) -> f64 {
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // The density over the image, changed to solid angle. The image spans 2π
+        // radians horizontally and π vertically.
+        let image_pdf = self.marginal.pdf(y) * self.rows[y].pdf(x);
+
+        image_pdf / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// The pixel seen in `direction`.
+    fn pixel(&self, direction: Vec3) -> (usize, usize) {
+        let local = rotate_y(direction.unit_vector(), -self.rotation);
+        let (u, v) = direction_to_uv(local);
+
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = (((1.0 - v) * self.height as f64) as usize).min(self.height - 1);
+
+        (x, y)
+    }
+}
+
+fn rotate_y(d: Vec3, angle: f64) -> Vec3 {
+    if angle == 0.0 {
+        return d;
+    }
+
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * d.x + sin * d.z, d.y, -sin * d.x + cos * d.z)
+}
+
+/// The sine of the polar angle at the center of row `y`.
+fn row_sin_theta(y: usize, height: usize) -> f64 {
+    (PI * (y as f64 + 0.5) / height as f64).sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    #[test]
+    fn environment_map_lookup() {
+        // Left half red, right half blue, bottom row dark.
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let dark = Color::new(0.1, 0.1, 0.1);
+        let map = EnvironmentMap::new(2, 2, vec![red, blue, dark, dark]);
+
+        assert_eq!(direction_to_uv(Vec3::new(0.0, 1.0, 0.0)).1, 1.0);
+        assert_eq!(direction_to_uv(Vec3::new(0.0, -1.0, 0.0)).1, 0.0);
+        assert_eq!(direction_to_uv(Vec3::new(1.0, 0.0, 0.0)), (0.5, 0.5));
+
+        assert_eq!(map.value(Vec3::new(0.0, 1.0, 1.0)), red);
+        assert_eq!(map.value(Vec3::new(0.0, 1.0, -1.0)), blue);
+        assert_eq!(map.value(Vec3::new(0.0, -1.0, 1.0)), dark);
+    }
+
+    #[test]
+    fn environment_map_rotation_and_intensity() {
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let map = EnvironmentMap::new(2, 1, vec![red, blue])
+            .with_rotation(180.0)
+            .with_intensity(2.0);
+
+        assert_eq!(map.value(Vec3::new(0.0, 0.5, 1.0)), 2.0 * blue);
+        assert_eq!(map.value(Vec3::new(0.0, 0.5, -1.0)), 2.0 * red);
+    }
+
+    #[test]
+    fn environment_map_sampling_follows_luminance() {
+        // A dim map with one bright "sun" pixel.
+        let (width, height) = (32, 16);
+        let mut pixels = vec![Color::new(0.1, 0.1, 0.1); width * height];
+        let sun = 5 * width + 20;
+        pixels[sun] = Color::new(1000.0, 1000.0, 1000.0);
+
+        let map = EnvironmentMap::new(width, height, pixels).with_rotation(30.0);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let mut sun_samples = 0;
+        for _ in 0..1000 {
+            let (direction, pdf) = map.sample(&mut rng);
+
+            let expected = map.pdf(direction);
+            assert!(
+                (pdf - expected).abs() <= 1e-6 * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+
+            let (x, y) = map.pixel(direction);
+            if y * width + x == sun {
+                sun_samples += 1;
+            }
+        }
+        assert!(sun_samples > 900, "{}", sun_samples);
+
+        // The density integrates to one over the sphere. The rotation is a whole number
+        // of pixels so the integration grid lines up with the pixels.
+        let map = map.with_rotation(45.0);
+        let (columns, rows) = (width * 8, height * 8);
+        let (d_phi, d_theta) = (2.0 * PI / columns as f64, PI / rows as f64);
+
+        let mut integral = 0.0;
+        for j in 0..rows {
+            let theta = (j as f64 + 0.5) * d_theta;
+            for i in 0..columns {
+                let phi = (i as f64 + 0.5) * d_phi;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                integral += map.pdf(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+    }
+}
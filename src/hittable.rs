@@ -1,19 +1,28 @@
-use crate::material::{Lambertian, Material};
+use crate::aabb::Aabb;
+use crate::hittable_list::HittableList;
+use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{Point3, Vec3};
 
+use rand::RngCore;
 use std::sync::Arc;
 
-#[derive(Clone)]
-pub struct HitRecord {
+/// Where a ray meets a surface. The record borrows the material of the object that was
+/// hit, so finding and passing on a hit costs no allocation or reference count update.
+#[derive(Clone, Copy)]
+pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
-    pub material: Arc<dyn Material + Send + Sync>,
+    pub material: &'a dyn Material,
+    /// The id of the object hit, given by the [`Tagged`] wrapper around it, 0 if untagged.
+    pub object_id: u32,
 }
 
-impl HitRecord {
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
         self.front_face = r.direction.dot(outward_normal) < 0.0;
 
@@ -25,36 +34,104 @@ impl HitRecord {
     }
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
+    /// A hit at `t` along `r`, with the normal turned against the ray.
     pub fn new(
-        p: Point3,
-        normal: Vec3,
+        r: Ray,
         t: f64,
-        front_face: bool,
-        material: Arc<dyn Material + Send + Sync>,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        material: &'a dyn Material,
     ) -> Self {
-        Self {
-            p,
-            normal,
+        let mut rec = Self {
+            p: r.at(t),
+            normal: outward_normal,
             t,
-            front_face,
+            u,
+            v,
+            front_face: true,
             material,
-        }
+            object_id: 0,
+        };
+        rec.set_face_normal(r, outward_normal);
+
+        rec
     }
 }
 
-impl Default for HitRecord {
-    fn default() -> Self {
-        Self::new(
-            Point3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.0,
-            false,
-            Arc::new(Lambertian::default()),
-        )
+pub trait Hittable: Send + Sync {
+    /// The closest hit with `t` between `t_min` and `t_max`, if any.
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Density, per unit solid angle, of [`random`](Self::random) returning `direction`
+    /// from `origin`. Zero for objects that cannot be sampled.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// A random direction from `origin` towards the object, drawn with `rng`.
+    fn random(&self, _origin: Point3, _rng: &mut dyn RngCore) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    /// Whether the object gives off light and should be sampled as a light source.
+    fn is_emissive(&self) -> bool {
+        false
     }
+
+    /// Adds the emissive objects held by a container to `lights`, however deep they are.
+    fn add_lights(&self, _lights: &mut HittableList) {}
+
+    /// Calls `f` with every material of the object, in order, to number them for the
+    /// material id AOV.
+    fn for_each_material(&self, _f: &mut dyn FnMut(&dyn Material)) {}
 }
 
-pub trait Hittable: Send + Sync {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+/// Gives every hit of the object it wraps an id, for the object id AOV.
+pub struct Tagged {
+    object: Arc<dyn Hittable + Send + Sync>,
+    id: u32,
+}
+
+impl Tagged {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, id: u32) -> Self {
+        Self { object, id }
+    }
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let rec = self.object.hit(r, t_min, t_max)?;
+
+        Some(HitRecord {
+            object_id: self.id,
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    fn add_lights(&self, lights: &mut HittableList) {
+        self.object.add_lights(lights)
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        self.object.for_each_material(f)
+    }
 }
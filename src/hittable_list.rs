@@ -1,5 +1,10 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
 use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
 use std::sync::Arc;
 
 #[derive(Default, Clone)]
@@ -21,23 +26,84 @@ impl HittableList {
     pub fn clear(&mut self) {
         self.objects.clear();
     }
+
+    /// The emissive objects of the list and of the lists and BVHs in it, to be sampled as
+    /// light sources.
+    pub fn lights(&self) -> HittableList {
+        let mut lights = HittableList::new();
+        self.add_lights(&mut lights);
+
+        lights
+    }
+}
+
+/// Adds `object` to `lights` if it gives off light, or the lights it holds if it is a
+/// container.
+pub(crate) fn add_lights(object: &Arc<dyn Hittable + Send + Sync>, lights: &mut HittableList) {
+    if object.is_emissive() {
+        lights.add(object.clone());
+    } else {
+        object.add_lights(lights);
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::default();
-        let mut hit_anything = false;
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = None;
         let mut closest_so_far = t_max;
 
         for object in &self.objects {
-            if object.hit(r, t_min, closest_so_far, &mut temp_rec) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-
-                *rec = temp_rec.clone();
+            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                closest = Some(rec);
             }
         }
 
-        hit_anything
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let mut output_box = objects.next()?.bounding_box()?;
+
+        for object in objects {
+            output_box = Aabb::surrounding_box(output_box, object.bounding_box()?);
+        }
+
+        Some(output_box)
+    }
+
+    /// Picks each object with equal probability.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        match self.objects.choose(rng) {
+            Some(object) => object.random(origin, rng),
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+
+    fn add_lights(&self, lights: &mut HittableList) {
+        for object in &self.objects {
+            add_lights(object, lights);
+        }
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        for object in &self.objects {
+            object.for_each_material(f);
+        }
     }
 }
@@ -0,0 +1,450 @@
+//! Estimates the light arriving along a ray by tracing paths through the scene.
+
+use crate::background::Background;
+use crate::environment::EnvironmentMap;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::pdf::{LightPdf, Pdf};
+use crate::ray::Ray;
+use crate::vec3::{Color, Point3, Vec3};
+
+use rand::RngCore;
+
+/// How paths find the light sources.
+///
+/// Emissive objects in the light list and environment map backgrounds can be reached
+/// both by sampling the materials and by sampling the lights. Every strategy converges to
+/// the same image, with different noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Only follows the directions picked by the materials.
+    Bsdf,
+    /// Sends a shadow ray towards the lights at every diffuse or glossy bounce and ignores
+    /// the lights the path runs into after such a bounce.
+    NextEvent,
+    /// Combines material and light sampling with the power heuristic.
+    #[default]
+    Mis,
+}
+
+/// What the previous bounce of a path knows about how the ray was chosen.
+#[derive(Clone, Copy)]
+enum Bounce {
+    /// Camera rays and rays leaving a mirror-like surface.
+    Specular,
+    /// A ray picked by the material at `origin` with density `pdf`.
+    Sampled { origin: Point3, pdf: f64 },
+}
+
+struct Context<'a> {
+    integrator: Integrator,
+    world: &'a dyn Hittable,
+    lights: &'a HittableList,
+    background: &'a Background,
+    environment: Option<&'a EnvironmentMap>,
+}
+
+impl Integrator {
+    pub const NAMES: [&'static str; 3] = ["bsdf", "nee", "mis"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bsdf" => Some(Integrator::Bsdf),
+            "nee" => Some(Integrator::NextEvent),
+            "mis" => Some(Integrator::Mis),
+            _ => None,
+        }
+    }
+
+    /// Follows `r` for up to `depth` bounces and returns the light it carries back. The
+    /// path only depends on the numbers drawn from `rng`.
+    pub fn ray_color(
+        self,
+        r: Ray,
+        world: &dyn Hittable,
+        lights: &HittableList,
+        background: &Background,
+        depth: i32,
+        rng: &mut dyn RngCore,
+    ) -> Color {
+        self.context(world, lights, background)
+            .trace(r, depth, Bounce::Specular, rng)
+    }
+
+    /// Like [`ray_color`](Self::ray_color), also returning the first surface `r` hits, for
+    /// the AOVs.
+    pub fn ray_color_and_hit<'w>(
+        self,
+        r: Ray,
+        world: &'w dyn Hittable,
+        lights: &HittableList,
+        background: &Background,
+        depth: i32,
+        rng: &mut dyn RngCore,
+    ) -> (Color, Option<HitRecord<'w>>) {
+        if depth <= 0 {
+            return (Color::default(), None);
+        }
+
+        let rec = world.hit(r, 0.001, f64::INFINITY);
+        let color =
+            self.context(world, lights, background)
+                .shade(r, rec, depth, Bounce::Specular, rng);
+
+        (color, rec)
+    }
+
+    fn context<'a>(
+        self,
+        world: &'a dyn Hittable,
+        lights: &'a HittableList,
+        background: &'a Background,
+    ) -> Context<'a> {
+        let environment = match background {
+            Background::Environment(map) => Some(map.as_ref()),
+            _ => None,
+        };
+
+        Context {
+            integrator: self,
+            world,
+            lights,
+            background,
+            environment,
+        }
+    }
+}
+
+impl Context<'_> {
+    fn trace(&self, r: Ray, depth: i32, bounce: Bounce, rng: &mut dyn RngCore) -> Color {
+        if depth <= 0 {
+            return Color::default();
+        }
+
+        let rec = self.world.hit(r, 0.001, f64::INFINITY);
+        self.shade(r, rec, depth, bounce, rng)
+    }
+
+    /// The light `r` carries back from `rec`, the closest hit along it, or from the
+    /// background if there is none.
+    fn shade(
+        &self,
+        r: Ray,
+        rec: Option<HitRecord>,
+        depth: i32,
+        bounce: Bounce,
+        rng: &mut dyn RngCore,
+    ) -> Color {
+        let Some(rec) = rec else {
+            let weight = match self.environment {
+                Some(_) => self.emission_weight(bounce, r.direction),
+                None => 1.0,
+            };
+
+            return weight * self.background.value(r.direction);
+        };
+
+        let emitted = if rec.material.is_emissive() {
+            self.emission_weight(bounce, r.direction) * rec.material.emitted(rec.u, rec.v, rec.p)
+        } else {
+            Color::default()
+        };
+
+        let Some(srec) = rec.material.scatter(&r, &rec, rng) else {
+            return emitted;
+        };
+
+        if srec.is_specular {
+            return emitted
+                + srec.attenuation * self.trace(srec.scattered, depth - 1, Bounce::Specular, rng);
+        }
+
+        // At the last bounce the material ray is not traced, so neither is the shadow ray
+        // that would find the light one bounce later than the other strategies.
+        let direct = match self.integrator {
+            Integrator::NextEvent | Integrator::Mis if depth > 1 => {
+                self.sample_lights(&r, &rec, srec.pdf.as_ref(), rng)
+            }
+            _ => Color::default(),
+        };
+        let bounce = Bounce::Sampled {
+            origin: rec.p,
+            pdf: srec.pdf.value(srec.scattered.direction),
+        };
+
+        emitted + direct + srec.attenuation * self.trace(srec.scattered, depth - 1, bounce, rng)
+    }
+
+    /// Picks one of the light sources with equal probability, then a direction from
+    /// `origin` towards it. `None` without light sources.
+    fn light_pdf(&self, origin: Point3) -> Option<LightPdf<'_>> {
+        LightPdf::new(self.lights, self.environment, origin)
+    }
+
+    /// Weight of light found by following the material after `bounce`.
+    fn emission_weight(&self, bounce: Bounce, direction: Vec3) -> f64 {
+        let Bounce::Sampled { origin, pdf } = bounce else {
+            return 1.0;
+        };
+
+        let light_pdf = || {
+            self.light_pdf(origin)
+                .map_or(0.0, |lights| lights.value(direction))
+        };
+
+        match self.integrator {
+            Integrator::Bsdf => 1.0,
+            Integrator::NextEvent if light_pdf() > 0.0 => 0.0,
+            Integrator::NextEvent => 1.0,
+            Integrator::Mis => power_heuristic(pdf, light_pdf()),
+        }
+    }
+
+    /// Light reaching `rec` from a direction picked among the light sources, already
+    /// multiplied by the material.
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        material_pdf: &dyn Pdf,
+        rng: &mut dyn RngCore,
+    ) -> Color {
+        let Some(lights) = self.light_pdf(rec.p) else {
+            return Color::default();
+        };
+
+        let direction = lights.generate(rng);
+        let light_pdf = lights.value(direction);
+        let shadow_ray = Ray::new(rec.p, direction);
+        let f = rec.material.eval(r, rec, &shadow_ray);
+        if light_pdf == 0.0 || f == Color::default() {
+            return Color::default();
+        }
+
+        let radiance = match self.world.hit(shadow_ray, 0.001, f64::INFINITY) {
+            Some(light_rec) => light_rec
+                .material
+                .emitted(light_rec.u, light_rec.v, light_rec.p),
+            None => self
+                .environment
+                .map_or(Color::default(), |map| map.value(direction)),
+        };
+
+        let weight = match self.integrator {
+            Integrator::Mis => power_heuristic(light_pdf, material_pdf.value(direction)),
+            _ => 1.0,
+        };
+
+        weight * f * radiance / light_pdf
+    }
+}
+
+/// Weight of a sample taken with density `pdf` when another strategy could have taken it
+/// with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian, Material, Metal};
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+
+    use rand::prelude::*;
+    use std::sync::Arc;
+
+    fn add_quad(
+        world: &mut HittableList,
+        corners: [Point3; 4],
+        material: Arc<dyn Material + Send + Sync>,
+    ) {
+        world.add(Arc::new(Triangle::new(
+            corners[0],
+            corners[1],
+            corners[2],
+            material.clone(),
+        )));
+        world.add(Arc::new(Triangle::new(
+            corners[0], corners[2], corners[3], material,
+        )));
+    }
+
+    fn ground(world: &mut HittableList, material: Arc<dyn Material + Send + Sync>) {
+        add_quad(
+            world,
+            [
+                Point3::new(-10.0, 0.0, -10.0),
+                Point3::new(10.0, 0.0, -10.0),
+                Point3::new(10.0, 0.0, 10.0),
+                Point3::new(-10.0, 0.0, 10.0),
+            ],
+            material,
+        );
+    }
+
+    /// Mean and standard error of `n` estimates of the red channel.
+    fn estimate(n: usize, mut sample: impl FnMut() -> Color) -> (f64, f64) {
+        let samples: Vec<f64> = (0..n).map(|_| sample().x).collect();
+
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n as f64 - 1.0);
+
+        (mean, (variance / n as f64).sqrt())
+    }
+
+    #[test]
+    fn direct_light_from_a_sphere() {
+        let mut world = HittableList::new();
+        ground(
+            &mut world,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        )));
+
+        let lights = world.lights();
+        assert_eq!(lights.objects.len(), 1);
+
+        // A sphere of radiance L seen at distance d gives an irradiance of π L r² / d², of
+        // which a diffuse surface reflects albedo / π.
+        let expected = 0.5 * 4.0 * 0.25 / 4.0;
+
+        let r = Ray::new(Point3::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0));
+        for integrator in [Integrator::NextEvent, Integrator::Mis] {
+            let mut rng = StdRng::seed_from_u64(1);
+            let (mean, _) = estimate(4000, || {
+                integrator.ray_color(r, &world, &lights, &Background::Black, 2, &mut rng)
+            });
+
+            assert!(
+                (mean - expected).abs() < 0.03 * expected,
+                "{:?}: {} {}",
+                integrator,
+                mean,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn integrators_agree_at_every_depth() {
+        // A diffuse floor under a large panel light.
+        let mut world = HittableList::new();
+        ground(
+            &mut world,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        add_quad(
+            &mut world,
+            [
+                Point3::new(-3.0, 2.0, -3.0),
+                Point3::new(-3.0, 2.0, 3.0),
+                Point3::new(3.0, 2.0, 3.0),
+                Point3::new(3.0, 2.0, -3.0),
+            ],
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        );
+        let lights = world.lights();
+
+        let r = Ray::new(Point3::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0));
+        let background = Background::Black;
+        for depth in 1..=3 {
+            let color = |integrator: Integrator| {
+                let mut rng = StdRng::seed_from_u64(depth as u64);
+                estimate(20_000, || {
+                    integrator.ray_color(r, &world, &lights, &background, depth, &mut rng)
+                })
+            };
+            let (bsdf, bsdf_error) = color(Integrator::Bsdf);
+
+            // The floor is only lit once the path can bounce off it.
+            if depth == 1 {
+                assert_eq!(bsdf, 0.0);
+            }
+
+            for integrator in [Integrator::NextEvent, Integrator::Mis] {
+                let (mean, error) = color(integrator);
+                let tolerance = 4.0 * (error.powi(2) + bsdf_error.powi(2)).sqrt();
+                assert!(
+                    (mean - bsdf).abs() <= tolerance,
+                    "depth {}: {:?} {} ± {}, bsdf {} ± {}",
+                    depth,
+                    integrator,
+                    mean,
+                    error,
+                    bsdf,
+                    bsdf_error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mis_matches_reference_on_glossy_metal() {
+        // A glossy floor reflecting a large dim panel and a small bright bulb.
+        let mut world = HittableList::new();
+        ground(
+            &mut world,
+            Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.2)),
+        );
+        add_quad(
+            &mut world,
+            [
+                Point3::new(-1.0, 1.5, 1.0),
+                Point3::new(1.0, 1.5, 1.0),
+                Point3::new(1.0, 1.5, 3.0),
+                Point3::new(-1.0, 1.5, 3.0),
+            ],
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        );
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.3, 1.0, 1.2),
+            0.05,
+            Arc::new(DiffuseLight::new(Color::new(200.0, 200.0, 200.0))),
+        )));
+        let lights = world.lights();
+
+        let r = Ray::new(Point3::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0));
+        let color = |integrator: Integrator| {
+            let (world, lights) = (&world, &lights);
+            let mut rng = StdRng::seed_from_u64(integrator as u64);
+            move || integrator.ray_color(r, world, lights, &Background::Black, 2, &mut rng)
+        };
+
+        // Every strategy is unbiased, so a high sample count of the plain ones is a
+        // reference for the combined one.
+        let (bsdf, bsdf_error) = estimate(100_000, color(Integrator::Bsdf));
+        let (nee, nee_error) = estimate(100_000, color(Integrator::NextEvent));
+        let (bsdf_weight, nee_weight) = (bsdf_error.powi(-2), nee_error.powi(-2));
+        let reference = (bsdf * bsdf_weight + nee * nee_weight) / (bsdf_weight + nee_weight);
+        let reference_error = (bsdf_weight + nee_weight).powf(-0.5);
+
+        let (mis, mis_error) = estimate(20_000, color(Integrator::Mis));
+
+        let tolerance = 4.0 * (mis_error.powi(2) + reference_error.powi(2)).sqrt();
+        assert!(
+            (mis - reference).abs() < tolerance,
+            "mis {} ± {}, reference {} ± {}",
+            mis,
+            mis_error,
+            reference,
+            reference_error
+        );
+
+        // Per sample, the combination is less noisy than either strategy alone.
+        let mis_deviation = mis_error * 20_000f64.sqrt();
+        assert!(mis_deviation < bsdf_error * 100_000f64.sqrt());
+        assert!(mis_deviation < nee_error * 100_000f64.sqrt());
+    }
+}
@@ -1,8 +1,29 @@
-pub mod vec3;
+pub mod aabb;
+pub mod adaptive;
+pub mod aov;
+pub mod background;
+pub mod bvh;
+pub mod camera;
 pub mod color;
-pub mod ray;
+pub mod denoise;
+pub mod environment;
 pub mod hittable;
-pub mod sphere;
 pub mod hittable_list;
-pub mod camera;
+pub mod integrator;
 pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod pdf;
+pub mod perlin;
+pub mod ray;
+pub mod renderer;
+pub mod scene;
+pub mod scenes;
+pub mod settings;
+pub mod sphere;
+pub mod texture;
+pub mod tonemap;
+pub mod triangle;
+pub mod vec3;
@@ -1,13 +1,48 @@
-use crate::{ray::Ray, hittable::HitRecord, vec3::{Color, Vec3}};
+use crate::{ray::Ray, hittable::HitRecord, pdf::{CosinePdf, DeltaPdf, FuzzyReflectionPdf, Pdf}, texture::{SolidColor, Texture}, vec3::{Color, Point3}};
 
 use rand::prelude::*;
 
+use std::sync::Arc;
+
+/// How a ray continues after hitting a surface.
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub scattered: Ray,
+    /// The distribution `scattered` was picked from.
+    pub pdf: Box<dyn Pdf>,
+    /// Mirror-like scattering into a single direction, which light sampling cannot
+    /// reach. `pdf` has no density then.
+    pub is_specular: bool,
+}
+
 pub trait Material {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool;
+    /// `None` when the ray is absorbed. Every random choice is drawn from `rng`.
+    fn scatter(&self, r: &Ray, rec: &HitRecord, rng: &mut dyn RngCore) -> Option<ScatterRecord>;
+
+    /// Light given off by the surface at `p`, black for everything but lights.
+    fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        Color::default()
+    }
+
+    /// The fraction of the light arriving along `scattered` that leaves towards the
+    /// origin of `r`, the BSDF times the cosine term. For scattering that is not specular
+    /// the attenuation `scatter` reports is `eval` divided by the density of its `pdf`.
+    fn eval(&self, _r: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::default()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// The color of the surface at `rec`, for the albedo AOV. Black for lights.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Default for Lambertian {
@@ -18,6 +53,10 @@ impl Default for Lambertian {
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>) -> Self {
         Self {
             albedo
         }
@@ -25,31 +64,37 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
-
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal
-        }
-
-        let new_scattered = Ray::new(rec.p, scatter_direction);
-
-        scattered.origin = new_scattered.origin;
-        scattered.direction = new_scattered.direction;
+    fn scatter(&self, _r: &Ray, rec: &HitRecord, rng: &mut dyn RngCore) -> Option<ScatterRecord> {
+        let pdf = CosinePdf::new(rec.normal);
+
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            scattered: Ray::new(rec.p, pdf.generate(rng)),
+            pdf: Box::new(pdf),
+            is_specular: false,
+        })
+    }
 
-        *attenuation = self.albedo;
+    fn eval(&self, _r: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        CosinePdf::new(rec.normal).value(scattered.direction) * self.albedo.value(rec.u, rec.v, rec.p)
+    }
 
-        true
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, rec.p)
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
             albedo,
             fuzz
@@ -64,15 +109,44 @@ impl Default for Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-       let reflected = r.direction.unit_vector().reflect(rec.normal);
+    /// The reflected direction is offset by a random point in a ball of radius `fuzz`.
+    /// Rays ending up below the surface are absorbed.
+    fn scatter(&self, r: &Ray, rec: &HitRecord, rng: &mut dyn RngCore) -> Option<ScatterRecord> {
+        let reflected = r.direction.unit_vector().reflect(rec.normal);
+
+        let (pdf, is_specular): (Box<dyn Pdf>, bool) = if self.fuzz > 0.0 {
+            (Box::new(FuzzyReflectionPdf::new(reflected, self.fuzz)), false)
+        } else {
+            (Box::new(DeltaPdf::new(reflected)), true)
+        };
 
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
+        let scattered = Ray::new(rec.p, pdf.generate(rng));
+        if scattered.direction.dot(rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            scattered,
+            pdf,
+            is_specular,
+        })
+    }
+
+    fn eval(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        if self.fuzz <= 0.0 || scattered.direction.dot(rec.normal) <= 0.0 {
+            return Color::default();
+        }
+
+        let reflected = r.direction.unit_vector().reflect(rec.normal);
+        let pdf = FuzzyReflectionPdf::new(reflected, self.fuzz);
 
-        *attenuation = self.albedo;
+        pdf.value(scattered.direction) * self.albedo.value(rec.u, rec.v, rec.p)
+    }
 
-        scattered.direction.dot(rec.normal) > 0.0
-    }    
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, rec.p)
+    }
 }
 
 
@@ -102,8 +176,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
+    fn scatter(&self, r: &Ray, rec: &HitRecord, rng: &mut dyn RngCore) -> Option<ScatterRecord> {
         let refraction_ratio = if rec.front_face  { 1.0 / self.ir } else { self.ir };
 
         let unit_direction =  r.direction.unit_vector();
@@ -112,16 +185,48 @@ impl Material for Dielectric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
-        let mut rng = thread_rng();
-
         let direction = if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>(){
             unit_direction.reflect(rec.normal)
         } else {
             unit_direction.refract(rec.normal, refraction_ratio)
         };
 
-        *scattered = Ray::new(rec.p, direction);
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            scattered: Ray::new(rec.p, direction),
+            pdf: Box::new(DeltaPdf::new(direction)),
+            is_specular: true,
+        })
+    }
+
+    /// Glass lets all the light through, whichever way it goes.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Color
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            emit
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r: &Ray, _rec: &HitRecord, _rng: &mut dyn RngCore) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.emit
+    }
 
+    fn is_emissive(&self) -> bool {
         true
     }
 }
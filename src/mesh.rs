@@ -0,0 +1,370 @@
+use crate::aabb::Aabb;
+use crate::bvh::BvhBuilder;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle;
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
+use std::sync::Arc;
+
+/// An indexed triangle mesh sharing one material.
+///
+/// Vertex attributes are stored once and referenced by index, and the triangles are
+/// kept in their own flat BVH, so the whole mesh is a single object of the scene.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[u32; 3]>,
+    nodes: Vec<MeshNode>,
+    /// Running total of the triangle areas, in BVH order, to sample points uniformly
+    /// over the surface.
+    area_cdf: Vec<f64>,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+/// A node of the mesh BVH, laid out depth first: the first child of an interior node
+/// directly follows it and `offset` points at the second one. For leaves `offset` is the
+/// first triangle of the node.
+#[derive(Debug, Clone, Copy)]
+struct MeshNode {
+    bbox: Aabb,
+    offset: u32,
+    count: u32,
+    axis: u8,
+}
+
+/// Nodes the traversal of the mesh BVH keeps to visit. Depth first, it holds at most the
+/// far child of every node above the current one and both children of the current one.
+const STACK_SIZE: usize = 64;
+
+/// Deepest level of the mesh BVH, the root being at 0. Deeper nodes become leaves, which
+/// only very clustered triangles ever need.
+const MAX_DEPTH: usize = STACK_SIZE - 1;
+
+impl TriangleMesh {
+    /// Panics if there are no triangles or if an index points past `positions`.
+    pub fn new(
+        positions: Vec<Point3>,
+        indices: Vec<[u32; 3]>,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        assert!(!indices.is_empty(), "Cannot build a mesh without triangles");
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "Mesh index out of range"
+        );
+
+        let mut mesh = Self {
+            positions,
+            normals: None,
+            uvs: None,
+            indices,
+            nodes: Vec::new(),
+            area_cdf: Vec::new(),
+            material,
+        };
+        mesh.build_bvh(&BvhBuilder::default());
+
+        mesh.area_cdf = (0..mesh.indices.len())
+            .scan(0.0, |total, i| {
+                *total += triangle::area(mesh.vertices(i));
+                Some(*total)
+            })
+            .collect();
+
+        mesh
+    }
+
+    /// Per-vertex normals, indexed like the positions.
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len(), "One normal per vertex");
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Per-vertex texture coordinates, indexed like the positions.
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len(), "One UV per vertex");
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn vertices(&self, triangle: usize) -> [Point3; 3] {
+        self.indices[triangle].map(|i| self.positions[i as usize])
+    }
+
+    fn build_bvh(&mut self, builder: &BvhBuilder) {
+        let bounds: Vec<Aabb> = (0..self.indices.len())
+            .map(|i| triangle::bounds(self.vertices(i)))
+            .collect();
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+
+        let mut nodes = Vec::new();
+        build_node(builder, &bounds, &mut order, 0, 0, &mut nodes);
+
+        // Store the triangles in leaf order so every leaf is a contiguous range.
+        self.indices = order.iter().map(|&i| self.indices[i]).collect();
+        self.nodes = nodes;
+    }
+}
+
+fn build_node(
+    builder: &BvhBuilder,
+    bounds: &[Aabb],
+    order: &mut [usize],
+    first: usize,
+    depth: usize,
+    nodes: &mut Vec<MeshNode>,
+) {
+    let bbox = order
+        .iter()
+        .map(|&i| bounds[i])
+        .reduce(Aabb::surrounding_box)
+        .expect("Mesh BVH node without triangles");
+
+    let index = nodes.len();
+    nodes.push(MeshNode {
+        bbox,
+        offset: first as u32,
+        count: order.len() as u32,
+        axis: 0,
+    });
+
+    if depth == MAX_DEPTH {
+        return;
+    }
+    let Some(mid) = builder.partition(bounds, order) else {
+        return;
+    };
+
+    // The builder splits along the longest axis of the centroid bounds.
+    let axis = order
+        .iter()
+        .map(|&i| Aabb::new(bounds[i].centroid(), bounds[i].centroid()))
+        .reduce(Aabb::surrounding_box)
+        .map_or(0, |centroids| centroids.longest_axis());
+
+    let (lower, upper) = order.split_at_mut(mid);
+    build_node(builder, bounds, lower, first, depth + 1, nodes);
+    let second = nodes.len();
+    build_node(builder, bounds, upper, first + mid, depth + 1, nodes);
+
+    nodes[index].offset = second as u32;
+    nodes[index].count = 0;
+    nodes[index].axis = axis as u8;
+}
+
+impl TriangleMesh {
+    /// The nearest triangle hit by `r` with the result of [`triangle::intersect`].
+    fn closest_hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(usize, (f64, f64, f64))> {
+        let mut closest: Option<(usize, (f64, f64, f64))> = None;
+        let mut closest_so_far = t_max;
+
+        let direction = [r.direction.x, r.direction.y, r.direction.z];
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node_index = stack[stack_len];
+            let node = &self.nodes[node_index];
+
+            if !node.bbox.hit(r, t_min, closest_so_far) {
+                continue;
+            }
+
+            if node.count > 0 {
+                let first = node.offset as usize;
+
+                for triangle in first..first + node.count as usize {
+                    if let Some(hit) =
+                        triangle::intersect(self.vertices(triangle), r, t_min, closest_so_far)
+                    {
+                        closest_so_far = hit.0;
+                        closest = Some((triangle, hit));
+                    }
+                }
+            } else {
+                // Visit the child on the side the ray comes from first.
+                let first_child = node_index + 1;
+                let second_child = node.offset as usize;
+
+                let (near, far) = if direction[node.axis as usize] < 0.0 {
+                    (second_child, first_child)
+                } else {
+                    (first_child, second_child)
+                };
+
+                stack[stack_len] = far;
+                stack[stack_len + 1] = near;
+                stack_len += 2;
+            }
+        }
+
+        closest
+    }
+
+    fn surface_area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (triangle, hit) = self.closest_hit(r, t_min, t_max)?;
+
+        let [i0, i1, i2] = self.indices[triangle].map(|i| i as usize);
+        let normals = self
+            .normals
+            .as_ref()
+            .map(|normals| [normals[i0], normals[i1], normals[i2]]);
+        let uvs = self.uvs.as_ref().map(|uvs| [uvs[i0], uvs[i1], uvs[i2]]);
+
+        Some(triangle::hit_record(
+            r,
+            self.vertices(triangle),
+            normals,
+            uvs,
+            hit,
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.nodes[0].bbox)
+    }
+
+    /// Samples points uniformly over the whole surface. Only the nearest hit along
+    /// `direction` is accounted for, so meshes hiding parts of themselves from `origin`
+    /// are slightly overweighted.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        match self.closest_hit(Ray::new(origin, direction), 0.001, f64::INFINITY) {
+            Some((triangle, (t, _, _))) => triangle::solid_angle_pdf(
+                self.vertices(triangle),
+                direction,
+                t,
+                self.surface_area(),
+            ),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        let target = rng.gen::<f64>() * self.surface_area();
+        let triangle = self
+            .area_cdf
+            .partition_point(|&total| total <= target)
+            .min(self.indices.len() - 1);
+
+        triangle::sample_point(self.vertices(triangle), rng) - origin
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        f(self.material.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::material::Lambertian;
+    use crate::triangle::Triangle;
+
+    #[test]
+    fn mesh_matches_separate_triangles() {
+        let material: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::default());
+        let size = 24;
+
+        // A bumpy height field, two triangles per grid cell.
+        let mut positions = Vec::new();
+        for z in 0..=size {
+            for x in 0..=size {
+                let height = ((x * 7 + z * 13) % 5) as f64 * 0.1;
+                positions.push(Point3::new(x as f64, height, z as f64));
+            }
+        }
+
+        let mut indices = Vec::new();
+        for z in 0..size {
+            for x in 0..size {
+                let i = (z * (size + 1) + x) as u32;
+                let j = i + size as u32 + 1;
+                indices.push([i, j, i + 1]);
+                indices.push([i + 1, j, j + 1]);
+            }
+        }
+
+        let mut list = HittableList::new();
+        for [a, b, c] in &indices {
+            list.add(Arc::new(Triangle::new(
+                positions[*a as usize],
+                positions[*b as usize],
+                positions[*c as usize],
+                material.clone(),
+            )));
+        }
+
+        let mesh = TriangleMesh::new(positions, indices, material);
+        assert_eq!(mesh.vertex_count(), 625);
+        assert_eq!(mesh.triangle_count(), 1152);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..1000 {
+            let origin = Point3::new(rng.gen_range(-2.0..26.0), 5.0, rng.gen_range(-2.0..26.0));
+            let direction = Vec3::new(rng.gen_range(-1.0..1.0), -1.0, rng.gen_range(-1.0..1.0));
+            let r = Ray::new(origin, direction);
+
+            let list_hit = list.hit(r, 0.001, f64::INFINITY);
+            let mesh_hit = mesh.hit(r, 0.001, f64::INFINITY);
+
+            assert_eq!(list_hit.is_some(), mesh_hit.is_some());
+            if let (Some(list_rec), Some(mesh_rec)) = (list_hit, mesh_hit) {
+                assert_eq!(list_rec.t, mesh_rec.t);
+                assert_eq!(list_rec.normal, mesh_rec.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_trees_fit_the_traversal_stack() {
+        let material: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::default());
+
+        // Triangles each 16 times farther than the one before, which the SAH peels off
+        // one at a time from the far end.
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for i in 0..200 {
+            let x = 16f64.powi(i);
+            let first = positions.len() as u32;
+            positions.push(Point3::new(x, 0.0, 0.0));
+            positions.push(Point3::new(2.0 * x, 1.0, 0.0));
+            positions.push(Point3::new(x, 0.0, 1.0));
+            indices.push([first, first + 1, first + 2]);
+        }
+
+        let mesh = TriangleMesh::new(positions, indices, material);
+        let r = Ray::new(Point3::new(-1.0, 0.25, 0.25), Vec3::new(1.0, 0.0, 0.0));
+        let rec = mesh.hit(r, 0.001, f64::INFINITY).unwrap();
+
+        assert!((rec.t - 2.25).abs() < 1e-12);
+    }
+}
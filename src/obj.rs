@@ -0,0 +1,601 @@
+//! Wavefront OBJ and MTL loading.
+//!
+//! Polygon faces are fan-triangulated. MTL materials are mapped onto the closest material
+//! the renderer has: transparent ones become [`Dielectric`], ones with a mirror
+//! illumination model (or a specular color and no diffuse one) become [`Metal`], emissive
+//! ones become [`DiffuseLight`] and everything else becomes [`Lambertian`].
+
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::vec3::{Color, Point3, Vec3};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub type MaterialMap = HashMap<String, Arc<dyn Material + Send + Sync>>;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Loads an OBJ file, together with the MTL libraries it references, as one
+/// [`TriangleMesh`] per material. Faces without a `usemtl` statement get
+/// `default_material`.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    default_material: Arc<dyn Material + Send + Sync>,
+) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+
+    let mut world = HittableList::new();
+    for mesh in parse_obj(open(path)?, path, default_material)? {
+        world.add(Arc::new(mesh));
+    }
+
+    Ok(world)
+}
+
+/// Parses OBJ statements from `reader`. `path` is used in error messages and to resolve
+/// `mtllib` statements relative to the OBJ file.
+pub fn parse_obj<R: BufRead>(
+    reader: R,
+    path: &Path,
+    default_material: Arc<dyn Material + Send + Sync>,
+) -> Result<Vec<TriangleMesh>, ObjError> {
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut texcoords: Vec<(f64, f64)> = Vec::new();
+
+    let mut materials = MaterialMap::new();
+    let mut groups = vec![MeshGroup::new(None, default_material)];
+    let mut current = 0;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args, keyword).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&args, keyword).map_err(error)?),
+            "vt" => {
+                let u = parse_float(args.first(), keyword).map_err(error)?;
+                let v = match args.get(1) {
+                    Some(_) => parse_float(args.get(1), keyword).map_err(error)?,
+                    None => 0.0,
+                };
+                texcoords.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        args.len()
+                    )));
+                }
+
+                let vertices = args
+                    .iter()
+                    .map(|arg| {
+                        parse_face_vertex(arg, positions.len(), texcoords.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+
+                let group = &mut groups[current];
+                let indices: Vec<u32> = vertices
+                    .iter()
+                    .map(|&vertex| group.vertex(vertex, &positions, &texcoords, &normals))
+                    .collect();
+
+                for i in 1..indices.len() - 1 {
+                    group.indices.push([indices[0], indices[i], indices[i + 1]]);
+                }
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(error("mtllib needs a file name".to_string()));
+                }
+
+                let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                for name in args {
+                    materials.extend(load_mtl(directory.join(name))?);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let material = materials
+                    .get(&name)
+                    .ok_or_else(|| error(format!("unknown material '{}'", name)))?;
+
+                current = match groups.iter().position(|g| g.name.as_ref() == Some(&name)) {
+                    Some(index) => index,
+                    None => {
+                        groups.push(MeshGroup::new(Some(name), material.clone()));
+                        groups.len() - 1
+                    }
+                };
+            }
+            // Grouping, smoothing groups, lines, points and free-form geometry carry
+            // nothing we can render.
+            _ => {}
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|group| !group.indices.is_empty())
+        .map(MeshGroup::into_mesh)
+        .collect())
+}
+
+/// The faces of one material, with the OBJ `v/vt/vn` triplets welded into shared
+/// vertices.
+struct MeshGroup {
+    name: Option<String>,
+    material: Arc<dyn Material + Send + Sync>,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    has_uvs: bool,
+    has_normals: bool,
+    indices: Vec<[u32; 3]>,
+}
+
+impl MeshGroup {
+    fn new(name: Option<String>, material: Arc<dyn Material + Send + Sync>) -> Self {
+        Self {
+            name,
+            material,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            has_uvs: true,
+            has_normals: true,
+            indices: Vec::new(),
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        vertex: FaceVertex,
+        positions: &[Point3],
+        texcoords: &[(f64, f64)],
+        normals: &[Vec3],
+    ) -> u32 {
+        let key = (vertex.position, vertex.texcoord, vertex.normal);
+
+        if let Some(&index) = self.vertices.get(&key) {
+            return index;
+        }
+
+        let index = self.positions.len() as u32;
+        self.positions.push(positions[vertex.position]);
+        self.uvs
+            .push(vertex.texcoord.map_or((0.0, 0.0), |t| texcoords[t]));
+        self.normals
+            .push(vertex.normal.map_or(Vec3::default(), |n| normals[n]));
+        self.has_uvs &= vertex.texcoord.is_some();
+        self.has_normals &= vertex.normal.is_some();
+        self.vertices.insert(key, index);
+
+        index
+    }
+
+    /// UVs and normals are only kept if every vertex of the group has them.
+    fn into_mesh(self) -> TriangleMesh {
+        let mut mesh = TriangleMesh::new(self.positions, self.indices, self.material);
+
+        if self.has_uvs {
+            mesh = mesh.with_uvs(self.uvs);
+        }
+        if self.has_normals {
+            mesh = mesh.with_normals(self.normals);
+        }
+
+        mesh
+    }
+}
+
+/// Loads every material of an MTL file.
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<MaterialMap, ObjError> {
+    let path = path.as_ref();
+
+    let materials = parse_mtl(open(path)?, path)?;
+
+    Ok(materials
+        .into_iter()
+        .map(|mtl| (mtl.name.clone(), mtl.to_material()))
+        .collect())
+}
+
+/// The subset of an MTL material the renderer understands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: Color,
+    /// `Ks`
+    pub specular: Color,
+    /// `Ke`
+    pub emission: Color,
+    /// `Ns`
+    pub shininess: f64,
+    /// `Ni`
+    pub optical_density: Option<f64>,
+    /// `d`, or `1 - Tr`
+    pub dissolve: f64,
+    /// `illum`
+    pub illumination_model: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtlKind {
+    Diffuse,
+    Metal,
+    Glass,
+    Light,
+}
+
+impl MtlMaterial {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::default(),
+            emission: Color::default(),
+            shininess: 0.0,
+            optical_density: None,
+            dissolve: 1.0,
+            illumination_model: 2,
+        }
+    }
+
+    pub fn kind(&self) -> MtlKind {
+        let is_black = |c: Color| c.x <= 0.0 && c.y <= 0.0 && c.z <= 0.0;
+
+        if !is_black(self.emission) {
+            MtlKind::Light
+        } else if self.dissolve < 1.0 || matches!(self.illumination_model, 4 | 6 | 7 | 9) {
+            MtlKind::Glass
+        } else if matches!(self.illumination_model, 3 | 5 | 8)
+            || (is_black(self.diffuse) && !is_black(self.specular))
+        {
+            MtlKind::Metal
+        } else {
+            MtlKind::Diffuse
+        }
+    }
+
+    /// Maps the Phong exponent onto `Metal` fuzziness, sharp highlights give clean mirrors.
+    pub fn fuzz(&self) -> f64 {
+        (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt().min(1.0)
+    }
+
+    pub fn to_material(&self) -> Arc<dyn Material + Send + Sync> {
+        match self.kind() {
+            MtlKind::Diffuse => Arc::new(Lambertian::new(self.diffuse)),
+            MtlKind::Metal => Arc::new(Metal::new(self.specular, self.fuzz())),
+            MtlKind::Glass => Arc::new(Dielectric::new(
+                self.optical_density.filter(|&ni| ni > 1.0).unwrap_or(1.5),
+            )),
+            MtlKind::Light => Arc::new(DiffuseLight::new(self.emission)),
+        }
+    }
+}
+
+/// Parses MTL statements from `reader`. `path` is only used in error messages.
+pub fn parse_mtl<R: BufRead>(reader: R, path: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword.starts_with('#') {
+            continue;
+        }
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(error("newmtl needs a name".to_string()));
+            }
+
+            materials.push(MtlMaterial::new(args.join(" ")));
+            continue;
+        }
+
+        let is_known = matches!(
+            keyword,
+            "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "illum"
+        );
+        let Some(material) = materials.last_mut() else {
+            if is_known {
+                return Err(error(format!("'{}' before any newmtl", keyword)));
+            }
+            continue;
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_vec3(&args, keyword).map_err(error)?,
+            "Ks" => material.specular = parse_vec3(&args, keyword).map_err(error)?,
+            "Ke" => material.emission = parse_vec3(&args, keyword).map_err(error)?,
+            "Ns" => material.shininess = parse_float(args.first(), keyword).map_err(error)?,
+            "Ni" => {
+                material.optical_density = Some(parse_float(args.first(), keyword).map_err(error)?)
+            }
+            "d" => material.dissolve = parse_float(args.first(), keyword).map_err(error)?,
+            "Tr" => material.dissolve = 1.0 - parse_float(args.first(), keyword).map_err(error)?,
+            "illum" => {
+                material.illumination_model = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| error("illum needs an integer model".to_string()))?
+            }
+            // Texture maps and the remaining Phong terms are not supported.
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ObjError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+fn parse_float(arg: Option<&&str>, keyword: &str) -> Result<f64, String> {
+    let arg = arg.ok_or_else(|| format!("'{}' is missing a value", keyword))?;
+
+    arg.parse()
+        .map_err(|_| format!("invalid number '{}' in '{}'", arg, keyword))
+}
+
+fn parse_vec3(args: &[&str], keyword: &str) -> Result<Vec3, String> {
+    if args.len() < 3 {
+        return Err(format!(
+            "'{}' needs 3 components, found {}",
+            keyword,
+            args.len()
+        ));
+    }
+
+    Ok(Vec3::new(
+        parse_float(args.first(), keyword)?,
+        parse_float(args.get(1), keyword)?,
+        parse_float(args.get(2), keyword)?,
+    ))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving 1-based and negative indices.
+fn parse_face_vertex(
+    arg: &str,
+    position_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = arg.split('/');
+
+    let position = resolve_index(parts.next(), position_count, "vertex", arg)?
+        .ok_or_else(|| format!("face vertex '{}' has no position", arg))?;
+    let texcoord = resolve_index(parts.next(), texcoord_count, "texture coordinate", arg)?;
+    let normal = resolve_index(parts.next(), normal_count, "normal", arg)?;
+
+    Ok(FaceVertex {
+        position,
+        texcoord,
+        normal,
+    })
+}
+
+fn resolve_index(
+    part: Option<&str>,
+    count: usize,
+    what: &str,
+    arg: &str,
+) -> Result<Option<usize>, String> {
+    let part = match part {
+        None | Some("") => return Ok(None),
+        Some(part) => part,
+    };
+
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("invalid {} index in '{}'", what, arg))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} out of range, {} defined so far",
+            what, index, count
+        ));
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+
+    fn parse(source: &str) -> Result<Vec<TriangleMesh>, ObjError> {
+        parse_obj(
+            source.as_bytes(),
+            Path::new("test.obj"),
+            Arc::new(Lambertian::default()),
+        )
+    }
+
+    #[test]
+    fn obj_triangulates_polygons() {
+        let meshes = parse(
+            "# a unit square and a triangle\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vn 0 0 1\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+             f -4/-4/-1 -3/-3/-1 -1/-1/-1\n",
+        )
+        .unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].triangle_count(), 3);
+        assert_eq!(meshes[0].vertex_count(), 4);
+
+        let world = &meshes[0];
+        let r = Ray::new(Point3::new(0.9, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = world.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.u - 0.9).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn obj_errors_report_line_numbers() {
+        let error = parse("v 0 0 0\nv 1 0 0\n\nf 1 2 3\n").err().unwrap();
+        assert!(
+            matches!(error, ObjError::Parse { line: 4, .. }),
+            "{}",
+            error
+        );
+        assert_eq!(
+            error.to_string(),
+            "test.obj:4: vertex index 3 out of range, 2 defined so far"
+        );
+
+        let error = parse("v 0 0 zero\n").err().unwrap();
+        assert!(
+            matches!(error, ObjError::Parse { line: 1, .. }),
+            "{}",
+            error
+        );
+
+        let error = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl missing\nf 1 2 3\n")
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, ObjError::Parse { line: 4, .. }),
+            "{}",
+            error
+        );
+
+        let error = load_obj("does/not/exist.obj", Arc::new(Lambertian::default()))
+            .err()
+            .expect("expected an io error");
+        assert!(matches!(error, ObjError::Io { .. }));
+    }
+
+    #[test]
+    fn mtl_maps_onto_materials() {
+        let materials = parse_mtl(
+            "newmtl paint\nKd 0.8 0.1 0.1\nKs 0.5 0.5 0.5\nNs 10\nillum 2\n\
+             newmtl chrome\nKd 0 0 0\nKs 0.9 0.9 0.9\nNs 1000\n\
+             newmtl glass\nKd 1 1 1\nNi 1.45\nd 0.1\nillum 4\n\
+             newmtl lamp\nKd 0 0 0\nKe 10 10 10\n"
+                .as_bytes(),
+            Path::new("test.mtl"),
+        )
+        .unwrap();
+
+        let kinds: Vec<MtlKind> = materials.iter().map(MtlMaterial::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MtlKind::Diffuse,
+                MtlKind::Metal,
+                MtlKind::Glass,
+                MtlKind::Light
+            ]
+        );
+
+        assert_eq!(materials[0].diffuse, Color::new(0.8, 0.1, 0.1));
+        assert!(materials[1].fuzz() < 0.05);
+        assert_eq!(materials[2].optical_density, Some(1.45));
+
+        let error = parse_mtl("Kd 1 1 1\n".as_bytes(), Path::new("test.mtl")).unwrap_err();
+        assert!(matches!(error, ObjError::Parse { line: 1, .. }));
+    }
+}
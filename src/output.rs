@@ -0,0 +1,435 @@
+use crate::aov::{Aov, Aovs};
+use crate::renderer::Framebuffer;
+use crate::tonemap::ToneMapping;
+
+use image::codecs::hdr::HdrEncoder;
+use image::{DynamicImage, ImageFormat, Rgb, Rgb32FImage};
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Ldr(ImageFormat),
+    OpenExr,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+    pub fn from_extension<S: AsRef<OsStr>>(extension: S) -> Option<Self> {
+        let extension = extension.as_ref().to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => ImageFormat::from_extension(extension).map(Self::from),
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref().extension().and_then(Self::from_extension)
+    }
+
+    pub fn is_high_dynamic_range(self) -> bool {
+        !matches!(self, OutputFormat::Ldr(_))
+    }
+}
+
+impl From<ImageFormat> for OutputFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::OpenExr => OutputFormat::OpenExr,
+            ImageFormat::Hdr => OutputFormat::Hdr,
+            format => OutputFormat::Ldr(format),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    Exr {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            OutputError::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            OutputError::Exr { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutputError::Io { source, .. } => Some(source),
+            OutputError::Image { source, .. } => Some(source),
+            OutputError::Exr { source, .. } => Some(source),
+        }
+    }
+}
+
+pub fn write_image<P: AsRef<Path>>(
+    framebuffer: &Framebuffer,
+    path: P,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> Result<(), OutputError> {
+    let path = path.as_ref();
+    let image_error = |source| OutputError::Image {
+        path: path.to_path_buf(),
+        source,
+    };
+    let io_error = |source| OutputError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    match format {
+        OutputFormat::Ldr(format) => framebuffer
+            .to_rgb_image(tone_mapping)
+            .save_with_format(path, format)
+            .map_err(image_error),
+        OutputFormat::OpenExr => DynamicImage::ImageRgb32F(to_rgb32f_image(framebuffer))
+            .save_with_format(path, ImageFormat::OpenExr)
+            .map_err(image_error),
+        OutputFormat::Hdr => {
+            let file = File::create(path).map_err(io_error)?;
+            let pixels: Vec<Rgb<f32>> = to_rgb32f_image(framebuffer).pixels().copied().collect();
+
+            HdrEncoder::new(BufWriter::new(file))
+                .encode(
+                    &pixels,
+                    framebuffer.width() as usize,
+                    framebuffer.height() as usize,
+                )
+                .map_err(image_error)
+        }
+        OutputFormat::Pfm => {
+            let file = File::create(path).map_err(io_error)?;
+            let mut writer = BufWriter::new(file);
+
+            write_pfm(framebuffer, &mut writer)
+                .and_then(|()| writer.flush())
+                .map_err(io_error)
+        }
+    }
+}
+
+pub fn write_image_with_aovs<P: AsRef<Path>>(
+    framebuffer: &Framebuffer,
+    aovs: &Aovs,
+    selected: &[Aov],
+    path: P,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> Result<(), OutputError> {
+    let path = path.as_ref();
+
+    if format == OutputFormat::OpenExr {
+        return write_exr_with_aovs(framebuffer, aovs, selected, path).map_err(|source| {
+            OutputError::Exr {
+                path: path.to_path_buf(),
+                source,
+            }
+        });
+    }
+
+    write_image(framebuffer, path, format, tone_mapping)?;
+    for &aov in selected {
+        let pass = if format.is_high_dynamic_range() {
+            aovs.get(aov).cloned()
+        } else {
+            aovs.preview(aov)
+        };
+        let Some(pass) = pass else {
+            continue;
+        };
+
+        write_image(&pass, aov_path(path, aov), format, &ToneMapping::default())?;
+    }
+
+    Ok(())
+}
+
+pub fn aov_path<P: AsRef<Path>>(path: P, aov: Aov) -> PathBuf {
+    let path = path.as_ref();
+
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(aov.name());
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    path.with_file_name(file_name)
+}
+
+fn write_exr_with_aovs(
+    framebuffer: &Framebuffer,
+    aovs: &Aovs,
+    selected: &[Aov],
+    path: &Path,
+) -> exr::error::Result<()> {
+    use exr::prelude::*;
+
+    let channel = |name: &str, pass: &Framebuffer, component: usize| {
+        let samples = pass.pixels().iter().map(|c| c[component] as f32).collect();
+        AnyChannel::new(name, FlatSamples::F32(samples))
+    };
+
+    let mut channels = SmallVec::new();
+    for (component, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(channel(name, framebuffer, component));
+    }
+    for &aov in selected {
+        let Some(pass) = aovs.get(aov) else {
+            continue;
+        };
+
+        for (component, name) in aov.channels().iter().enumerate() {
+            let name = format!("{}.{}", aov.name(), name);
+            channels.push(channel(&name, pass, component));
+        }
+    }
+
+    let size = (framebuffer.width() as usize, framebuffer.height() as usize);
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::SMALL_LOSSLESS,
+        AnyChannels::sort(channels),
+    );
+
+    Image::from_layer(layer).write().to_file(path)
+}
+
+pub fn write_pfm<W: Write>(framebuffer: &Framebuffer, writer: &mut W) -> io::Result<()> {
+    // A negative scale marks little-endian data.
+    write!(
+        writer,
+        "PF\n{} {}\n-1.0\n",
+        framebuffer.width(),
+        framebuffer.height()
+    )?;
+
+    for y in (0..framebuffer.height()).rev() {
+        for x in 0..framebuffer.width() {
+            let color = framebuffer.get(x, y);
+
+            for channel in [color.x, color.y, color.z] {
+                writer.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn to_rgb32f_image(framebuffer: &Framebuffer) -> Rgb32FImage {
+    Rgb32FImage::from_fn(framebuffer.width(), framebuffer.height(), |x, y| {
+        let color = framebuffer.get(x, y);
+        Rgb([color.x as f32, color.y as f32, color.z as f32])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aov::AovSums;
+    use crate::vec3::Color;
+
+    use image::codecs::hdr::HdrDecoder;
+    use std::io::BufReader;
+
+    /// Radiance well above 1, with a different color in every pixel.
+    fn bright_framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let i = (y * 3 + x) as f64;
+                framebuffer.set(x, y, Color::new(0.25 * i, 12.5 + i, 100.0 - 7.0 * i));
+            }
+        }
+
+        framebuffer
+    }
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ray_tracing_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn output_formats_from_paths() {
+        assert_eq!(
+            OutputFormat::from_path("render.EXR"),
+            Some(OutputFormat::OpenExr)
+        );
+        assert_eq!(
+            OutputFormat::from_path("render.hdr"),
+            Some(OutputFormat::Hdr)
+        );
+        assert_eq!(
+            OutputFormat::from_path("render.pfm"),
+            Some(OutputFormat::Pfm)
+        );
+        assert_eq!(
+            OutputFormat::from_path("render.png"),
+            Some(OutputFormat::Ldr(ImageFormat::Png))
+        );
+        assert_eq!(OutputFormat::from_path("render"), None);
+        assert!(!OutputFormat::Ldr(ImageFormat::Png).is_high_dynamic_range());
+    }
+
+    #[test]
+    fn hdr_formats_keep_radiance_above_one() {
+        let framebuffer = bright_framebuffer();
+
+        let exr = temporary_path("radiance.exr");
+        write_image(
+            &framebuffer,
+            &exr,
+            OutputFormat::OpenExr,
+            &ToneMapping::default(),
+        )
+        .unwrap();
+        let read = image::open(&exr).unwrap().into_rgb32f();
+        std::fs::remove_file(&exr).unwrap();
+        for (x, y, pixel) in read.enumerate_pixels() {
+            let color = framebuffer.get(x, y);
+            assert_eq!(pixel.0, [color.x as f32, color.y as f32, color.z as f32]);
+        }
+
+        // RGBE keeps 8 bits of precision relative to the brightest channel.
+        let hdr = temporary_path("radiance.hdr");
+        write_image(
+            &framebuffer,
+            &hdr,
+            OutputFormat::Hdr,
+            &ToneMapping::default(),
+        )
+        .unwrap();
+        let decoder = HdrDecoder::new(BufReader::new(File::open(&hdr).unwrap())).unwrap();
+        let read = decoder.read_image_hdr().unwrap();
+        std::fs::remove_file(&hdr).unwrap();
+        for (i, pixel) in read.iter().enumerate() {
+            let color = framebuffer.get(i as u32 % 3, i as u32 / 3);
+            let brightest = color.x.max(color.y).max(color.z);
+            for (read, expected) in pixel.0.iter().zip([color.x, color.y, color.z]) {
+                assert!((*read as f64 - expected).abs() < brightest / 128.0);
+            }
+        }
+
+        let mut pfm = Vec::new();
+        write_pfm(&framebuffer, &mut pfm).unwrap();
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        let floats: Vec<f32> = pfm[header.len()..]
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats.len(), 18);
+        // The bottom row comes first.
+        assert_eq!(&floats[..3], &[0.75, 15.5, 79.0]);
+        assert_eq!(&floats[9..12], &[0.0, 12.5, 100.0]);
+    }
+
+    #[test]
+    fn aovs_go_into_exr_channels_or_files_of_their_own() {
+        use exr::prelude::*;
+
+        let framebuffer = bright_framebuffer();
+        // Nothing hit anywhere.
+        let aovs = Aovs::from_sums(3, 2, Some(&[AovSums::default(); 6]), &[1; 6]);
+        let selected = [Aov::Albedo, Aov::Depth];
+        let tone_mapping = ToneMapping::default();
+
+        assert_eq!(
+            aov_path("renders/final.png", Aov::Albedo),
+            Path::new("renders/final.albedo.png")
+        );
+
+        let exr = temporary_path("aovs.exr");
+        write_image_with_aovs(
+            &framebuffer,
+            &aovs,
+            &selected,
+            &exr,
+            OutputFormat::OpenExr,
+            &tone_mapping,
+        )
+        .unwrap();
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(&exr)
+            .unwrap();
+        std::fs::remove_file(&exr).unwrap();
+
+        let channels = &image.layer_data.channel_data.list;
+        let names: Vec<String> = channels.iter().map(|c| c.name.to_string()).collect();
+        assert_eq!(
+            names,
+            ["B", "G", "R", "albedo.B", "albedo.G", "albedo.R", "depth.Z"]
+        );
+        let samples = |index: usize| channels[index].sample_data.values_as_f32().collect();
+        let red: Vec<f32> = samples(2);
+        assert_eq!(red, [0.0, 0.25, 0.5, 0.75, 1.0, 1.25]);
+        let depth: Vec<f32> = samples(6);
+        assert!(depth.iter().all(|&depth| depth == f32::INFINITY));
+
+        let pfm = temporary_path("aovs.pfm");
+        write_image_with_aovs(
+            &framebuffer,
+            &aovs,
+            &selected,
+            &pfm,
+            OutputFormat::Pfm,
+            &tone_mapping,
+        )
+        .unwrap();
+        for path in [
+            pfm.clone(),
+            aov_path(&pfm, Aov::Albedo),
+            aov_path(&pfm, Aov::Depth),
+        ] {
+            assert!(std::fs::read(&path).unwrap().starts_with(b"PF\n3 2\n"));
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // Without the first hits, only the sample counts are there to write.
+        let untraced = Aovs::from_sums(3, 2, None, &[1; 6]);
+        write_image_with_aovs(
+            &framebuffer,
+            &untraced,
+            &[Aov::Albedo, Aov::SampleCount],
+            &pfm,
+            OutputFormat::Pfm,
+            &tone_mapping,
+        )
+        .unwrap();
+        assert!(!aov_path(&pfm, Aov::Albedo).exists());
+        for path in [pfm.clone(), aov_path(&pfm, Aov::SampleCount)] {
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Probability densities over directions, used to pick scattered rays and to weight
+//! them.
+
+use crate::environment::EnvironmentMap;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::onb::Onb;
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
+
+use std::f64::consts::PI;
+
+pub trait Pdf {
+    /// Density of `generate` returning `direction`, per unit solid angle. Zero for
+    /// distributions concentrated on single directions.
+    fn value(&self, direction: Vec3) -> f64;
+
+    /// A random direction drawn with `rng`, not necessarily normalized.
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3;
+}
+
+/// Directions around `w` with a density proportional to the cosine of their angle with
+/// it, the distribution of ideal diffuse reflection.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(w: Vec3) -> Self {
+        Self {
+            uvw: Onb::build_from_w(w),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine = direction.unit_vector().dot(self.uvw.w);
+
+        if cosine <= 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+
+        self.uvw.local(Vec3::new(x, y, z))
+    }
+}
+
+/// A unit direction offset by a point picked uniformly in a ball of radius `fuzz`, the
+/// glossy reflection of [`Metal`](crate::material::Metal).
+pub struct FuzzyReflectionPdf {
+    reflected: Vec3,
+    fuzz: f64,
+}
+
+impl FuzzyReflectionPdf {
+    /// `fuzz` must be positive.
+    pub fn new(reflected: Vec3, fuzz: f64) -> Self {
+        Self {
+            reflected: reflected.unit_vector(),
+            fuzz,
+        }
+    }
+}
+
+impl Pdf for FuzzyReflectionPdf {
+    /// The density of a direction is the volume of the ball along it.
+    fn value(&self, direction: Vec3) -> f64 {
+        let direction = direction.unit_vector();
+
+        // The ray from the origin along `direction` enters the ball at distance `near` and
+        // leaves it at `far`. In spherical coordinates the volume in between is
+        // (far³ - near³) / 3 per unit solid angle.
+        let cosine = direction.dot(self.reflected);
+        let discriminant = cosine * cosine - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+
+        let far = cosine + discriminant.sqrt();
+        let near = (cosine - discriminant.sqrt()).max(0.0);
+        if far <= 0.0 {
+            return 0.0;
+        }
+
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        self.reflected + self.fuzz * Vec3::random_in_unit_sphere(rng)
+    }
+}
+
+/// Always the same direction, for mirrors and glass.
+pub struct DeltaPdf {
+    direction: Vec3,
+}
+
+impl DeltaPdf {
+    pub fn new(direction: Vec3) -> Self {
+        Self { direction }
+    }
+}
+
+impl Pdf for DeltaPdf {
+    fn value(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn generate(&self, _rng: &mut dyn RngCore) -> Vec3 {
+        self.direction
+    }
+}
+
+/// Directions from `origin` towards the surface of an object.
+pub struct HittablePdf<'a> {
+    object: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Point3) -> Self {
+        Self { object, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.object.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        self.object.random(self.origin, rng)
+    }
+}
+
+/// Directions towards the bright parts of an environment map.
+pub struct EnvironmentPdf<'a> {
+    map: &'a EnvironmentMap,
+}
+
+impl<'a> EnvironmentPdf<'a> {
+    pub fn new(map: &'a EnvironmentMap) -> Self {
+        Self { map }
+    }
+}
+
+impl Pdf for EnvironmentPdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.map.pdf(direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        self.map.sample(rng).0
+    }
+}
+
+/// Picks one of several distributions with equal probability.
+pub struct MixturePdf<'a> {
+    pdfs: Vec<Box<dyn Pdf + 'a>>,
+}
+
+impl<'a> MixturePdf<'a> {
+    /// Panics if `pdfs` is empty.
+    pub fn new(pdfs: Vec<Box<dyn Pdf + 'a>>) -> Self {
+        assert!(!pdfs.is_empty(), "Mixture without distributions");
+
+        Self { pdfs }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        let sum: f64 = self.pdfs.iter().map(|pdf| pdf.value(direction)).sum();
+
+        sum / self.pdfs.len() as f64
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        let choice = rng.gen_range(0..self.pdfs.len());
+
+        self.pdfs[choice].generate(rng)
+    }
+}
+
+/// Picks one of `lights` or the environment map with equal probability, then a direction
+/// from `origin` towards it. The mixture of their [`HittablePdf`]s and [`EnvironmentPdf`],
+/// without boxing them for every bounce.
+pub struct LightPdf<'a> {
+    lights: &'a HittableList,
+    environment: Option<&'a EnvironmentMap>,
+    origin: Point3,
+}
+
+impl<'a> LightPdf<'a> {
+    /// `None` without light sources.
+    pub fn new(
+        lights: &'a HittableList,
+        environment: Option<&'a EnvironmentMap>,
+        origin: Point3,
+    ) -> Option<Self> {
+        if lights.objects.is_empty() && environment.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            lights,
+            environment,
+            origin,
+        })
+    }
+
+    fn source_count(&self) -> usize {
+        self.lights.objects.len() + self.environment.is_some() as usize
+    }
+}
+
+impl Pdf for LightPdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        let lights = self.lights.objects.iter();
+        let sum: f64 = lights
+            .map(|light| light.pdf_value(self.origin, direction))
+            .chain(self.environment.map(|map| map.pdf(direction)))
+            .sum();
+
+        sum / self.source_count() as f64
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Vec3 {
+        let choice = rng.gen_range(0..self.source_count());
+
+        match (self.lights.objects.get(choice), self.environment) {
+            (Some(light), _) => light.random(self.origin, rng),
+            (None, Some(map)) => map.sample(rng).0,
+            (None, None) => unreachable!("LightPdf without light sources"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use crate::sphere::Sphere;
+
+    use std::sync::Arc;
+
+    /// Integrates `pdf` over the sphere and checks that it agrees with `generate`.
+    fn check_pdf(pdf: &dyn Pdf) {
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let n = 200_000;
+        let integral = (0..n)
+            .map(|_| pdf.value(Vec3::random_unit_vector(&mut rng)) * 4.0 * PI)
+            .sum::<f64>()
+            / n as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+        for _ in 0..1000 {
+            assert!(pdf.value(pdf.generate(&mut rng)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn pdfs_integrate_to_one() {
+        let normal = Vec3::new(1.0, 2.0, -0.5).unit_vector();
+
+        check_pdf(&CosinePdf::new(normal));
+        check_pdf(&FuzzyReflectionPdf::new(normal, 0.5));
+        check_pdf(&FuzzyReflectionPdf::new(normal, 1.0));
+        check_pdf(&MixturePdf::new(vec![
+            Box::new(CosinePdf::new(normal)),
+            Box::new(FuzzyReflectionPdf::new(-normal, 0.8)),
+        ]));
+
+        let mut lights = HittableList::new();
+        for center in [Point3::new(0.0, 3.0, 0.0), Point3::new(-2.0, 0.0, 1.0)] {
+            let material = Arc::new(DiffuseLight::new(Vec3::new(1.0, 1.0, 1.0)));
+            lights.add(Arc::new(Sphere::new(center, 1.0, material)));
+        }
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        check_pdf(&LightPdf::new(&lights, None, origin).unwrap());
+        assert!(LightPdf::new(&HittableList::new(), None, origin).is_none());
+    }
+}
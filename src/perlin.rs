@@ -0,0 +1,151 @@
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise on a lattice of random unit vectors.
+///
+/// The generator is built from a seed so the same seed always gives the same noise.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .unit_vector()
+            })
+            .collect();
+
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        let perm_x = permutation();
+        let perm_y = permutation();
+        let perm_z = permutation();
+
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Smooth noise roughly in `[-1, 1]`, zero at every lattice point.
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+
+                    let (a, b, c) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - a, v - b, w - c);
+
+                    // Trilinear interpolation with Hermite smoothed weights.
+                    accum += blend(u, a) * blend(v, b) * blend(w, c) * gradient.dot(weight);
+                }
+            }
+        }
+
+        accum
+    }
+
+    /// Sum of `octaves` layers of the absolute noise, each twice the frequency and half
+    /// the amplitude of the previous one. Never negative.
+    pub fn turbulence(&self, p: Point3, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            accum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+
+        accum
+    }
+
+    /// Fractional Brownian motion: like [`turbulence`](Self::turbulence) but keeping the
+    /// sign of every octave, so the result is centered on zero.
+    pub fn fbm(&self, p: Point3, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+
+        accum
+    }
+}
+
+fn wrap(i: i64) -> usize {
+    (i & (POINT_COUNT as i64 - 1)) as usize
+}
+
+/// Weight of the lattice corner `corner` (0 or 1) at the fractional coordinate `t`.
+fn blend(t: f64, corner: f64) -> f64 {
+    let smooth = t * t * (3.0 - 2.0 * t);
+
+    corner * smooth + (1.0 - corner) * (1.0 - smooth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perlin_noise_is_seeded_and_smooth() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(1);
+        let c = Perlin::new(2);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut differs = false;
+        for _ in 0..1000 {
+            let p = Point3::new(
+                rng.gen_range(-50.0..50.0),
+                rng.gen_range(-50.0..50.0),
+                rng.gen_range(-50.0..50.0),
+            );
+            let noise = a.noise(p);
+
+            assert_eq!(noise, b.noise(p));
+            differs |= noise != c.noise(p);
+
+            assert!((-1.0..=1.0).contains(&noise), "{}", noise);
+            assert!((a.noise(p + Vec3::new(1e-6, 0.0, 0.0)) - noise).abs() < 1e-5);
+            assert!(a.turbulence(p, 7) >= 0.0);
+        }
+        assert!(differs);
+
+        assert_eq!(a.noise(Point3::new(3.0, -7.0, 12.0)), 0.0);
+    }
+}
@@ -0,0 +1,100 @@
+use ray_tracing::renderer::Progress;
+
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30;
+
+/// Minimum time between two redraws, so fast renders do not flood the terminal.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A progress bar redrawn in place on standard error.
+pub struct ProgressBar {
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        Self {
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    pub fn update(&self, progress: &Progress) {
+        let finished = progress.tiles_done == progress.tile_count;
+
+        let mut last_draw = self.last_draw.lock().unwrap();
+        if !finished && last_draw.is_some_and(|time| time.elapsed() < REDRAW_INTERVAL) {
+            return;
+        }
+        *last_draw = Some(Instant::now());
+
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r{}\x1b[K", format_progress(progress));
+        let _ = stderr.flush();
+    }
+
+    /// Moves past the bar, once the render is over.
+    pub fn finish(&self) {
+        if self.last_draw.lock().unwrap().is_some() {
+            eprintln!();
+        }
+    }
+}
+
+fn format_progress(progress: &Progress) -> String {
+    let filled = (progress.fraction() * BAR_WIDTH as f64) as usize;
+    let eta = match progress.eta() {
+        Some(eta) => format_duration(eta),
+        None => "-".to_string(),
+    };
+
+    format!(
+        "[{}{}] {:>3}%  {}/{} tiles  {:.2} Msamples/s  {:.2} Mrays/s  ETA {}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        (progress.fraction() * 100.0) as u32,
+        progress.tiles_done,
+        progress.tile_count,
+        progress.samples_per_second() / 1e6,
+        progress.rays_per_second() / 1e6,
+        eta,
+    )
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour on.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64().round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_line() {
+        let progress = Progress {
+            tiles_done: 3,
+            tile_count: 12,
+            samples: 250_000,
+            sample_count: 1_000_000,
+            rays: 1_500_000,
+            elapsed: Duration::from_secs(20),
+        };
+
+        assert_eq!(
+            format_progress(&progress),
+            "[#######-----------------------]  25%  3/12 tiles  0.01 Msamples/s  0.07 Mrays/s  \
+             ETA 1:00"
+        );
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+}
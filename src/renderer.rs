@@ -0,0 +1,1121 @@
+//! Renders a scene into a [`Framebuffer`] of linear colors.
+//!
+//! The image is split into square tiles that worker threads pick up as they become free,
+//! so a tile full of glass or deep bounces does not hold the others back.
+//!
+//! Every sample of every pixel draws its random numbers from its own generator, seeded
+//! from the render seed and the sample's position. The same seed and settings give the
+//! same image however many threads render it and in whatever order.
+//!
+//! Renders can be progressive, taking a few samples of every pixel per pass and summing
+//! them in an [`Accumulator`] to show the image as it converges.
+//!
+//! With [`Renderer::aovs`], the first hits of the camera rays also make up the
+//! [`Aovs`] passes. The sample counts are kept either way.
+//!
+//! With [`Renderer::adaptive_sampling`], pixels stop taking samples once they and the
+//! pixels around them have converged. They are checked between passes, so that a pixel
+//! whose few samples all missed the light is kept going by its noisy neighbours.
+//!
+//! A render reports its [`Progress`] after every tile and stops early when its
+//! [`CancelToken`] is cancelled. The pixels it did not get to are black, or keep the
+//! samples of the passes before.
+
+use crate::aabb::Aabb;
+use crate::adaptive::{AdaptiveSampling, RunningVariance};
+use crate::aov::{AovSums, Aovs, MaterialIds};
+use crate::background::Background;
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::color::luminance;
+use crate::hittable::{HitRecord, Hittable, Tagged};
+use crate::hittable_list::HittableList;
+use crate::integrator::Integrator;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Color;
+
+use image::{Rgb, RgbImage};
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The average radiance reaching every pixel, row by row from the top of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    /// A black image.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    /// 8 bit sRGB colors, brought into the displayable range by `tone_mapping`.
+    pub fn to_rgb_image(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let (r, g, b) = tone_mapping.to_rgb8(self.get(x, y));
+            Rgb([r, g, b])
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel outside the image");
+
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+/// The sum of the samples taken so far for every pixel, and how many there are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    width: u32,
+    height: u32,
+    sums: Vec<Color>,
+    samples: Vec<u32>,
+    luminances: Vec<RunningVariance>,
+    aovs: Option<Vec<AovSums>>,
+}
+
+impl Accumulator {
+    /// An image without samples.
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixel_count = width as usize * height as usize;
+
+        Self {
+            width,
+            height,
+            sums: vec![Color::default(); pixel_count],
+            samples: vec![0; pixel_count],
+            luminances: vec![RunningVariance::default(); pixel_count],
+            aovs: None,
+        }
+    }
+
+    /// An image without samples that also sums up the AOVs.
+    fn with_aovs(width: u32, height: u32) -> Self {
+        let pixel_count = width as usize * height as usize;
+
+        Self {
+            aovs: Some(vec![AovSums::default(); pixel_count]),
+            ..Self::new(width, height)
+        }
+    }
+
+    pub fn sum(&self, x: u32, y: u32) -> Color {
+        self.sums[self.index(x, y)]
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    /// The variance of the luminance of the samples of the pixel.
+    pub fn variance(&self, x: u32, y: u32) -> f64 {
+        self.luminances[self.index(x, y)].variance()
+    }
+
+    /// The average of the samples of every pixel, black for pixels without any.
+    pub fn framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let samples = self.samples(x, y);
+                if samples > 0 {
+                    framebuffer.set(x, y, self.sum(x, y) / samples as f64);
+                }
+            }
+        }
+
+        framebuffer
+    }
+
+    /// The AOVs of the samples so far. Only renders that record them have the passes that
+    /// [need the first hits](crate::aov::Aov::needs_first_hits).
+    pub fn aovs(&self) -> Aovs {
+        Aovs::from_sums(self.width, self.height, self.aovs.as_deref(), &self.samples)
+    }
+
+    /// Which pixels have converged, along with the 3×3 pixels around them.
+    fn converged(&self, adaptive: &AdaptiveSampling) -> Vec<bool> {
+        let own: Vec<bool> = self
+            .luminances
+            .iter()
+            .map(|luminance| adaptive.converged(luminance))
+            .collect();
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        (0..own.len())
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                (y.saturating_sub(1)..(y + 2).min(height)).all(|ny| {
+                    (x.saturating_sub(1)..(x + 2).min(width)).all(|nx| own[ny * width + nx])
+                })
+            })
+            .collect()
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> PixelSums {
+        let index = self.index(x, y);
+
+        PixelSums {
+            color: self.sums[index],
+            samples: self.samples[index],
+            luminance: self.luminances[index],
+            aovs: self.aovs.as_ref().map(|aovs| aovs[index]),
+        }
+    }
+
+    fn record(&mut self, x: u32, y: u32, pixel: PixelSums) {
+        let index = self.index(x, y);
+        self.sums[index] = pixel.color;
+        self.samples[index] = pixel.samples;
+        self.luminances[index] = pixel.luminance;
+        if let (Some(aovs), Some(pixel_aovs)) = (&mut self.aovs, pixel.aovs) {
+            aovs[index] = pixel_aovs;
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel outside the image");
+
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+/// The sums of the samples of one pixel.
+#[derive(Debug, Clone, Copy)]
+struct PixelSums {
+    color: Color,
+    samples: u32,
+    luminance: RunningVariance,
+    aovs: Option<AovSums>,
+}
+
+/// A pass of a progressive render, just finished.
+pub struct Pass<'a> {
+    /// Counted from 0.
+    pub index: usize,
+    pub pass_count: usize,
+    pub image: &'a Accumulator,
+}
+
+impl Pass<'_> {
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.pass_count
+    }
+}
+
+/// A rectangle of pixels rendered as one unit of work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Covers a `width` by `height` image with tiles of side `size`, smaller along the right
+/// and bottom edges when the size does not divide the image.
+pub fn tiles(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    assert!(size > 0, "Tiles must not be empty");
+
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(size as usize) {
+        for x in (0..width).step_by(size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            });
+        }
+    }
+
+    tiles
+}
+
+/// How far a render has got, reported after every tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tile_count: usize,
+    /// Samples taken so far, over all pixels.
+    pub samples: u64,
+    /// Samples the whole render takes, fewer as pixels converge with adaptive sampling.
+    pub sample_count: u64,
+    /// Rays traced through the scene so far, camera, scattered and shadow rays alike.
+    pub rays: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The fraction of the samples taken, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.sample_count == 0 {
+            return 1.0;
+        }
+
+        self.samples as f64 / self.sample_count as f64
+    }
+
+    /// 0 before any time has passed.
+    pub fn samples_per_second(&self) -> f64 {
+        self.per_second(self.samples)
+    }
+
+    /// 0 before any time has passed.
+    pub fn rays_per_second(&self) -> f64 {
+        self.per_second(self.rays)
+    }
+
+    fn per_second(&self, count: u64) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+
+        count as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The time left if the rest of the image renders as fast as what is done. `None`
+    /// before any sample is taken.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.samples == 0 {
+            return None;
+        }
+
+        let remaining = (self.sample_count - self.samples) as f64 / self.samples as f64;
+
+        Some(self.elapsed.mul_f64(remaining))
+    }
+}
+
+/// Asks a render to stop. Clones share the same state, so one can be handed to the
+/// renderer and another kept to cancel it from anywhere.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Cancels the render once `flag` is set, for flags set by signal handlers.
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self { cancelled: flag }
+    }
+}
+
+type ProgressCallback = dyn Fn(&Progress) + Send + Sync;
+
+/// Everything needed to render an image. The world goes into a BVH when rendering.
+pub struct Renderer {
+    world: HittableList,
+    lights: HittableList,
+    background: Background,
+    camera: Camera,
+    settings: RenderSettings,
+    integrator: Integrator,
+    tile_size: u32,
+    threads: Option<usize>,
+    seed: u64,
+    aovs: bool,
+    adaptive: Option<AdaptiveSampling>,
+    on_progress: Option<Box<ProgressCallback>>,
+    cancel: CancelToken,
+}
+
+/// The world of a render, in a BVH.
+struct World {
+    bvh: BvhNode,
+    /// Empty unless the render records the AOVs.
+    material_ids: MaterialIds,
+}
+
+impl Renderer {
+    /// Rendering panics if `world` is empty or holds an object without a bounding box.
+    pub fn new(world: &HittableList, camera: Camera, settings: RenderSettings) -> Self {
+        Self {
+            world: world.clone(),
+            lights: world.lights(),
+            background: Background::default(),
+            camera,
+            settings,
+            integrator: Integrator::default(),
+            tile_size: 16,
+            threads: None,
+            seed: 0,
+            aovs: false,
+            adaptive: None,
+            on_progress: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Renders `scene` as it describes itself: its camera, settings and background.
+    pub fn from_scene(scene: &Scene) -> Self {
+        Self::new(&scene.world, scene.camera(), scene.settings).background(scene.background.clone())
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: u32) -> Self {
+        assert!(tile_size > 0, "Tiles must not be empty");
+
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// The number of worker threads, by default one per core.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Seeds the random numbers of every sample.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Whether to also record the AOVs of the first hits of the camera rays. The objects
+    /// and materials of the world are numbered from 1, in order, for the id AOVs.
+    pub fn aovs(mut self, aovs: bool) -> Self {
+        self.aovs = aovs;
+        self
+    }
+
+    /// Stops sampling the pixels that have converged. The samples per pixel of the
+    /// settings become the most a pixel takes, and single pass renders take passes of
+    /// the minimum samples instead.
+    pub fn adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Calls `callback` after every tile, from the thread that rendered it.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Renders the image, or the part of it done before the render was cancelled.
+    pub fn render(&self) -> Framebuffer {
+        let pass_samples = match self.adaptive {
+            Some(adaptive) => adaptive.min_samples,
+            None => self.settings.samples_per_pixel,
+        };
+
+        self.render_progressive(pass_samples, |_| {}).framebuffer()
+    }
+
+    /// Renders the image in passes of `pass_samples` samples per pixel and calls
+    /// `on_pass` with the image so far after each of them. The final image is the same as
+    /// the one [`render`](Self::render) gives, and comes with the AOVs when they are
+    /// recorded.
+    ///
+    /// A cancelled render stops in the middle of a pass. The pixels it did not get to
+    /// keep the samples of the previous passes. With adaptive sampling, pixels are checked
+    /// before every pass, and the image depends on the size of the passes.
+    pub fn render_progressive<F>(&self, pass_samples: u32, mut on_pass: F) -> Accumulator
+    where
+        F: FnMut(&Pass),
+    {
+        assert!(pass_samples > 0, "Passes must take samples");
+
+        let RenderSettings {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            ..
+        } = self.settings;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads.unwrap_or(0))
+            .build()
+            .expect("Could not start the render threads");
+
+        let world = self.world();
+        let tiles = tiles(image_width, image_height, self.tile_size);
+        let pass_count = samples_per_pixel.div_ceil(pass_samples) as usize;
+        let tally = Tally::new(
+            tiles.len() * pass_count,
+            image_width as u64 * image_height as u64 * samples_per_pixel as u64,
+        );
+
+        let mut accumulator = if self.aovs {
+            Accumulator::with_aovs(image_width, image_height)
+        } else {
+            Accumulator::new(image_width, image_height)
+        };
+        for index in 0..pass_count {
+            let first = index as u32 * pass_samples;
+            let samples = first..(first + pass_samples).min(samples_per_pixel);
+
+            let converged = self
+                .adaptive
+                .map(|adaptive| accumulator.converged(&adaptive));
+            pool.install(|| {
+                self.render_pass(
+                    &world,
+                    &tiles,
+                    samples,
+                    converged.as_deref(),
+                    &mut accumulator,
+                    &tally,
+                )
+            });
+            if self.cancel.is_cancelled() {
+                break;
+            }
+
+            on_pass(&Pass {
+                index,
+                pass_count,
+                image: &accumulator,
+            });
+        }
+
+        accumulator
+    }
+
+    /// The world in a BVH, with its objects given ids if the render records the AOVs.
+    fn world(&self) -> World {
+        if !self.aovs {
+            return World {
+                bvh: BvhNode::new(&self.world),
+                material_ids: MaterialIds::default(),
+            };
+        }
+
+        let mut tagged = HittableList::new();
+        for (index, object) in self.world.objects.iter().enumerate() {
+            tagged.add(Arc::new(Tagged::new(object.clone(), index as u32 + 1)));
+        }
+
+        World {
+            bvh: BvhNode::new(&tagged),
+            material_ids: MaterialIds::new(&self.world),
+        }
+    }
+
+    /// Takes the samples numbered `samples` of every pixel, but the `converged` ones.
+    fn render_pass(
+        &self,
+        world: &World,
+        tiles: &[Tile],
+        samples: Range<u32>,
+        converged: Option<&[bool]>,
+        accumulator: &mut Accumulator,
+        tally: &Tally,
+    ) {
+        let rendered: Vec<Vec<PixelSums>> = tiles
+            .par_iter()
+            .map(|tile| {
+                let rays = AtomicU64::new(0);
+                let sums =
+                    self.render_tile(world, *tile, samples.clone(), converged, accumulator, &rays);
+                if sums.is_empty() {
+                    return sums;
+                }
+
+                let taken: u64 = sums
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pixel)| {
+                        let (x, y) = (i as u32 % tile.width, i as u32 / tile.width);
+                        (pixel.samples - accumulator.samples(tile.x + x, tile.y + y)) as u64
+                    })
+                    .sum();
+                let progress = tally.add(
+                    sums.len() == tile.width as usize * tile.height as usize,
+                    taken,
+                    sums.len() as u64 * samples.len() as u64 - taken,
+                    rays.into_inner(),
+                );
+                if let Some(callback) = &self.on_progress {
+                    callback(&progress);
+                }
+
+                sums
+            })
+            .collect();
+
+        for (tile, sums) in tiles.iter().zip(rendered) {
+            for (i, pixel) in sums.into_iter().enumerate() {
+                let (x, y) = (i as u32 % tile.width, i as u32 / tile.width);
+                accumulator.record(tile.x + x, tile.y + y, pixel);
+            }
+        }
+    }
+
+    /// The sums of the samples of the pixels of `tile` once `samples` are added to those
+    /// not `converged`, row by row from its top left corner, up to the pixel being rendered
+    /// when the render was cancelled. Adds the rays traced to `rays`.
+    fn render_tile(
+        &self,
+        world: &World,
+        tile: Tile,
+        samples: Range<u32>,
+        converged: Option<&[bool]>,
+        accumulator: &Accumulator,
+        rays: &AtomicU64,
+    ) -> Vec<PixelSums> {
+        let mut sums = Vec::with_capacity(tile.width as usize * tile.height as usize);
+        let counter = RayCounter {
+            world: &world.bvh,
+            rays,
+        };
+
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                if self.cancel.is_cancelled() {
+                    return sums;
+                }
+
+                let pixel = accumulator.pixel(x, y);
+                if converged.is_some_and(|converged| converged[accumulator.index(x, y)]) {
+                    sums.push(pixel);
+                } else {
+                    let rendered = self.render_pixel(
+                        &counter,
+                        &world.material_ids,
+                        x,
+                        y,
+                        samples.clone(),
+                        pixel,
+                    );
+                    sums.push(rendered);
+                }
+            }
+        }
+
+        sums
+    }
+
+    /// Adds the samples numbered `samples` of the pixel to its sums. Adding them one at a
+    /// time, whatever the passes, keeps progressive renders identical to the others.
+    fn render_pixel(
+        &self,
+        world: &dyn Hittable,
+        material_ids: &MaterialIds,
+        x: u32,
+        y: u32,
+        samples: Range<u32>,
+        mut sums: PixelSums,
+    ) -> PixelSums {
+        let RenderSettings {
+            image_width,
+            image_height,
+            max_depth,
+            ..
+        } = self.settings;
+
+        // The camera counts rows from the bottom of the image.
+        let j = image_height - 1 - y;
+        let pixel = y as u64 * image_width as u64 + x as u64;
+
+        for sample in samples {
+            let mut rng = sample_rng(self.seed, pixel, sample as u64);
+
+            let u = (x as f64 + rng.gen::<f64>()) / (image_width - 1).max(1) as f64;
+            let v = (j as f64 + rng.gen::<f64>()) / (image_height - 1).max(1) as f64;
+
+            let r = self.camera.get_ray(u, v, &mut rng);
+            let (color, rec) = self.integrator.ray_color_and_hit(
+                r,
+                world,
+                &self.lights,
+                &self.background,
+                max_depth,
+                &mut rng,
+            );
+            if let (Some(aovs), Some(rec)) = (&mut sums.aovs, rec) {
+                let material_id = material_ids.get(rec.material);
+                aovs.add(&rec, self.camera.depth(rec.p), material_id);
+            }
+            sums.color = sums.color + color;
+            sums.samples += 1;
+            sums.luminance.add(luminance(color));
+        }
+
+        sums
+    }
+}
+
+/// The totals of a render, shared by its workers.
+struct Tally {
+    start: Instant,
+    tile_count: usize,
+    sample_count: AtomicU64,
+    tiles_done: AtomicUsize,
+    samples: AtomicU64,
+    rays: AtomicU64,
+}
+
+impl Tally {
+    fn new(tile_count: usize, sample_count: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            tile_count,
+            sample_count: AtomicU64::new(sample_count),
+            tiles_done: AtomicUsize::new(0),
+            samples: AtomicU64::new(0),
+            rays: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds the work done on one tile, `finished` unless the render was cancelled in
+    /// the middle of it, and returns the progress so far. `skipped` samples were not
+    /// taken because their pixels had converged.
+    fn add(&self, finished: bool, samples: u64, skipped: u64, rays: u64) -> Progress {
+        let tiles_done = if finished {
+            self.tiles_done.fetch_add(1, Ordering::Relaxed) + 1
+        } else {
+            self.tiles_done.load(Ordering::Relaxed)
+        };
+
+        Progress {
+            tiles_done,
+            tile_count: self.tile_count,
+            samples: self.samples.fetch_add(samples, Ordering::Relaxed) + samples,
+            sample_count: self.sample_count.fetch_sub(skipped, Ordering::Relaxed) - skipped,
+            rays: self.rays.fetch_add(rays, Ordering::Relaxed) + rays,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
+
+/// Counts the rays traced through the world it wraps.
+struct RayCounter<'a> {
+    world: &'a dyn Hittable,
+    rays: &'a AtomicU64,
+}
+
+impl Hittable for RayCounter<'_> {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.rays.fetch_add(1, Ordering::Relaxed);
+
+        self.world.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.world.bounding_box()
+    }
+}
+
+/// The generator of sample `sample` of pixel `pixel`, pixels being numbered row by row.
+/// xoshiro256++ draws the same numbers on every platform and with every version of rand,
+/// so images stay bit-identical.
+fn sample_rng(seed: u64, pixel: u64, sample: u64) -> Xoshiro256PlusPlus {
+    Xoshiro256PlusPlus::seed_from_u64(mix(mix(mix(seed) ^ pixel) ^ sample))
+}
+
+/// The SplitMix64 finalizer, so that nearby inputs give unrelated seeds.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aov::Aov;
+    use crate::camera::CameraSettings;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian};
+    use crate::scenes;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+
+    use std::sync::Mutex;
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let (width, height) = (37, 23);
+
+        for size in [1, 8, 16, 64] {
+            let mut covered = vec![0; (width * height) as usize];
+            for tile in tiles(width, height, size) {
+                assert!(tile.width > 0 && tile.height > 0);
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(y * width + x) as usize] += 1;
+                    }
+                }
+            }
+
+            assert!(covered.iter().all(|&count| count == 1), "size {}", size);
+        }
+    }
+
+    #[test]
+    fn renders_every_pixel_right_side_up() {
+        let light = Color::new(0.25, 0.5, 0.75);
+        let settings = RenderSettings {
+            image_width: 37,
+            image_height: 23,
+            samples_per_pixel: 2,
+            max_depth: 4,
+        };
+        let camera = CameraSettings {
+            aperture: 0.0,
+            vfov: 90.0,
+            ..CameraSettings::default()
+        };
+
+        // Seen from inside, a light sphere fills the whole image.
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            camera.lookfrom,
+            100.0,
+            Arc::new(DiffuseLight::new(light)),
+        )));
+        let framebuffer = Renderer::new(&world, camera.build(settings.aspect_ratio()), settings)
+            .tile_size(8)
+            .threads(3)
+            .render();
+
+        assert_eq!((framebuffer.width(), framebuffer.height()), (37, 23));
+        assert!(framebuffer.pixels().iter().all(|&color| color == light));
+
+        // Outside of it, the sky is bluer at the top of the image.
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1.0,
+            Arc::new(DiffuseLight::new(light)),
+        )));
+        let framebuffer = Renderer::new(&world, camera.build(settings.aspect_ratio()), settings)
+            .tile_size(8)
+            .render();
+
+        assert!(framebuffer.get(18, 0).x < framebuffer.get(18, 22).x);
+    }
+
+    #[test]
+    fn records_the_aovs_of_the_first_hits() {
+        let settings = RenderSettings {
+            image_width: 21,
+            image_height: 11,
+            samples_per_pixel: 4,
+            max_depth: 4,
+        };
+        let camera = CameraSettings {
+            lookfrom: Point3::new(0.0, 0.0, 10.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 30.0,
+            aperture: 0.0,
+            ..CameraSettings::default()
+        };
+
+        // A glass ball on the right and a red one on the left, against the sky. The ids
+        // follow the world, not the image.
+        let red = Color::new(0.8, 0.1, 0.1);
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(2.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Dielectric::new(1.5)),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(-2.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(red)),
+        )));
+
+        let renderer = |aovs| {
+            Renderer::new(&world, camera.build(settings.aspect_ratio()), settings)
+                .tile_size(4)
+                .aovs(aovs)
+        };
+        let image = renderer(true).render_progressive(4, |_| {});
+        assert!(image.framebuffer() == renderer(false).render());
+
+        // Renders without AOVs still count their samples.
+        let untraced = renderer(false).render_progressive(4, |_| {}).aovs();
+        assert!(untraced.get(Aov::Albedo).is_none());
+        assert_eq!(untraced.get(Aov::SampleCount).unwrap().get(3, 3).x, 4.0);
+
+        // Only renders with AOVs wrap the objects to give them ids.
+        let ray = Ray::new(Point3::new(2.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let object_id = |aovs| {
+            let world = renderer(aovs).world();
+            world.bvh.hit(ray, 0.001, f64::INFINITY).unwrap().object_id
+        };
+        assert_eq!((object_id(false), object_id(true)), (0, 1));
+
+        let aovs = image.aovs();
+        let value = |aov, x, y| aovs.get(aov).unwrap().get(x, y);
+
+        for (x, center, id, albedo) in [
+            (6, Point3::new(-2.0, 0.0, 0.0), 2.0, red),
+            (
+                14,
+                Point3::new(2.0, 0.0, 0.0),
+                1.0,
+                Color::new(1.0, 1.0, 1.0),
+            ),
+        ] {
+            assert_eq!(value(Aov::Albedo, x, 5), albedo);
+            assert_eq!(value(Aov::ObjectId, x, 5).x, id);
+            assert_eq!(value(Aov::MaterialId, x, 5).x, id);
+
+            let normal = value(Aov::Normal, x, 5);
+            let position = value(Aov::Position, x, 5);
+            assert!(normal.z > 0.8 && normal.length() <= 1.0 + 1e-9);
+            assert!(((position - center).length() - 1.0).abs() < 0.05);
+            assert!((value(Aov::Depth, x, 5).x - (10.0 - position.z)).abs() < 1e-6);
+        }
+
+        assert_eq!(value(Aov::Depth, 0, 0).x, f64::INFINITY);
+        assert_eq!(value(Aov::ObjectId, 0, 0).x, 0.0);
+        assert_eq!(value(Aov::MaterialId, 0, 0).x, 0.0);
+        assert_eq!(value(Aov::Albedo, 0, 0), Color::default());
+
+        // Pixels on the edge of a ball take their ids from the samples that hit it.
+        for y in 0..settings.image_height {
+            for x in 0..settings.image_width {
+                let hit = value(Aov::Depth, x, y).x.is_finite();
+                assert_eq!(value(Aov::ObjectId, x, y).x > 0.0, hit);
+                assert_eq!(value(Aov::MaterialId, x, y).x, value(Aov::ObjectId, x, y).x);
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling_spends_samples_on_noisy_pixels() {
+        let settings = RenderSettings {
+            image_width: 16,
+            image_height: 8,
+            samples_per_pixel: 64,
+            max_depth: 8,
+        };
+        let camera = CameraSettings {
+            lookfrom: Point3::new(0.0, 0.0, 10.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.0,
+            ..CameraSettings::default()
+        };
+
+        // A diffuse ball in front of the sky, whose pixels converge at once.
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+
+        let samples = Arc::new(Mutex::new(None));
+        let recorded = samples.clone();
+        let renderer = Renderer::new(&world, camera.build(settings.aspect_ratio()), settings)
+            .tile_size(4)
+            .threads(1)
+            .adaptive_sampling(AdaptiveSampling {
+                min_samples: 8,
+                threshold: 0.01,
+            })
+            .on_progress(move |progress| {
+                *recorded.lock().unwrap() = Some((progress.samples, progress.sample_count))
+            });
+        let image = renderer.render_progressive(8, |_| {});
+
+        assert_eq!(image.samples(0, 0), 8);
+        assert_eq!(image.samples(8, 4), 64);
+        assert!(image.variance(8, 4) > image.variance(0, 0));
+
+        let taken: u64 = image.samples.iter().map(|&samples| samples as u64).sum();
+        assert!(taken < 16 * 8 * 64);
+        assert_eq!(*samples.lock().unwrap(), Some((taken, taken)));
+
+        // Single pass renders check the pixels as often.
+        assert!(renderer.render() == image.framebuffer());
+    }
+
+    #[test]
+    fn renders_are_reproducible_across_threads_and_tiles() {
+        let mut scene = scenes::builtin("random", 0).unwrap();
+        scene.settings.set_image_size(24, Some(16), None).unwrap();
+        scene.settings.samples_per_pixel = 2;
+        scene.settings.max_depth = 8;
+
+        let reference = Renderer::from_scene(&scene).seed(7).threads(1).render();
+        for (threads, tile_size) in [(1, 4), (3, 16), (4, 5)] {
+            let framebuffer = Renderer::from_scene(&scene)
+                .seed(7)
+                .threads(threads)
+                .tile_size(tile_size)
+                .render();
+
+            assert!(framebuffer == reference, "{} threads", threads);
+        }
+
+        let other_seed = Renderer::from_scene(&scene).seed(8).render();
+        assert!(other_seed != reference);
+
+        // The sample generators must not change with the platform or the rand version.
+        let mut rng = sample_rng(7, 3, 1);
+        assert_eq!(
+            [rng.next_u64(), rng.next_u64()],
+            [5391199867379850055, 8340975550564984734]
+        );
+    }
+
+    #[test]
+    fn reports_progress_and_stops_when_cancelled() {
+        let mut scene = scenes::builtin("random", 0).unwrap();
+        scene.settings.set_image_size(32, Some(16), None).unwrap();
+        scene.settings.samples_per_pixel = 2;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let full = Renderer::from_scene(&scene)
+            .tile_size(8)
+            .threads(1)
+            .on_progress(move |progress| recorded.lock().unwrap().push(*progress))
+            .render();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 8);
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].samples < pair[1].samples));
+
+        let last = events.last().unwrap();
+        assert_eq!((last.tiles_done, last.tile_count), (8, 8));
+        assert_eq!((last.samples, last.sample_count), (1024, 1024));
+        assert_eq!(last.eta(), Some(Duration::ZERO));
+        assert!(last.rays > last.samples);
+
+        let instant = Progress {
+            elapsed: Duration::ZERO,
+            ..*last
+        };
+        assert_eq!(instant.samples_per_second(), 0.0);
+        assert_eq!(instant.rays_per_second(), 0.0);
+
+        // Cancelling after the third tile leaves the others black.
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        let partial = Renderer::from_scene(&scene)
+            .tile_size(8)
+            .threads(1)
+            .cancel_token(cancel.clone())
+            .on_progress(move |progress| {
+                if progress.tiles_done == 3 {
+                    token.cancel();
+                }
+            })
+            .render();
+
+        assert!(cancel.is_cancelled());
+        for y in 0..16 {
+            for x in 0..32 {
+                let tile = (y / 8) * 4 + x / 8;
+                let expected = if tile < 3 {
+                    full.get(x, y)
+                } else {
+                    Color::default()
+                };
+                assert_eq!(partial.get(x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn progressive_renders_converge_to_the_same_image() {
+        let mut scene = scenes::builtin("random", 3).unwrap();
+        scene.settings.set_image_size(20, Some(12), None).unwrap();
+        scene.settings.samples_per_pixel = 8;
+        let renderer = Renderer::from_scene(&scene).seed(5).tile_size(8);
+
+        let mut passes = Vec::new();
+        let progressive = renderer.render_progressive(3, |pass| {
+            passes.push((pass.index, pass.pass_count, pass.is_last()));
+            assert_eq!(
+                pass.image.samples(19, 11),
+                (3 * pass.index as u32 + 3).min(8)
+            );
+
+            let average = pass.image.framebuffer().get(4, 7);
+            assert_eq!(
+                average,
+                pass.image.sum(4, 7) / pass.image.samples(4, 7) as f64
+            );
+        });
+
+        assert_eq!(passes, [(0, 3, false), (1, 3, false), (2, 3, true)]);
+        assert!(progressive.framebuffer() == renderer.render());
+    }
+}
@@ -0,0 +1,915 @@
+//! Declarative scene files.
+//!
+//! A scene is a JSON document with the camera, the render settings, tables of named
+//! textures and materials and the list of objects referencing them:
+//!
+//! ```json
+//! {
+//!     "camera": { "lookfrom": [13, 2, 3], "lookat": [0, 0, 0], "vfov": 20 },
+//!     "render": { "image_width": 400, "samples_per_pixel": 50 },
+//!     "background": { "type": "environment", "path": "studio.hdr" },
+//!     "textures": {
+//!         "checker": { "type": "checker", "even": [0.2, 0.3, 0.1], "odd": [0.9, 0.9, 0.9] }
+//!     },
+//!     "materials": {
+//!         "ground": { "type": "lambertian", "albedo": "checker" },
+//!         "glass": { "type": "dielectric", "ir": 1.5 },
+//!         "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] }
+//!     },
+//!     "objects": [
+//!         { "type": "sphere", "center": [0, -1000, 0], "radius": 1000, "material": "ground" },
+//!         { "type": "sphere", "center": [0, 1, 0], "radius": 1, "material": "glass" },
+//!         { "type": "sphere", "center": [0, 4, 0], "radius": 0.5, "material": "lamp" },
+//!         { "type": "mesh", "path": "teapot.obj", "material": "ground" }
+//!     ]
+//! }
+//! ```
+//!
+//! Every camera and render field is optional and defaults to the values of
+//! [`CameraSettings::default`] and [`RenderSettings::default`]. The image size is given
+//! by `image_width` and either `image_height` or `aspect_ratio`.
+//!
+//! The background is one of `black`, `solid` (`color`), `gradient` (`horizon` and
+//! `zenith`) or `environment` (`path` to an equirectangular `.hdr` or `.exr` image,
+//! optional `rotation` in degrees and `intensity`) and defaults to the sky gradient.
+//!
+//! Textures are `solid` (`color`), `checker` (`even`, `odd` and the cube size `scale`),
+//! `uv_checker` (`even`, `odd`, `columns` and `rows`), `image` (`path` to a PNG or
+//! JPEG file) or one of the procedural `noise`, `marble` (`base` and `vein` colors) and
+//! `wood` (`light` and `dark` colors), which take a frequency `scale` and a `seed`.
+//! The `albedo` of `lambertian` and `metal` materials is either a color or the name of
+//! a texture.
+//!
+//! Mesh, environment and image paths are relative to the scene file.
+
+use crate::background::Background;
+use crate::camera::{Camera, CameraSettings};
+use crate::environment::EnvironmentMap;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::obj::{self, ObjError};
+use crate::settings::RenderSettings;
+use crate::sphere::Sphere;
+use crate::texture::{
+    CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture, WoodTexture,
+};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: CameraSettings,
+    pub settings: RenderSettings,
+    pub background: Background,
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::from_json(&source, path)
+    }
+
+    /// Parses a scene document. `path` is used in error messages and to resolve mesh
+    /// paths, it does not need to exist.
+    pub fn from_json(source: &str, path: &Path) -> Result<Self, SceneError> {
+        let description: SceneDescription =
+            serde_json::from_str(source).map_err(|source| SceneError::Json {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        description.build(path)
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera.build(self.settings.aspect_ratio())
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+    Obj(ObjError),
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Json { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Invalid { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Obj(error) => error.fmt(f),
+            SceneError::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Json { source, .. } => Some(source),
+            SceneError::Invalid { .. } => None,
+            SceneError::Obj(error) => Some(error),
+            SceneError::Image { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(error: ObjError) -> Self {
+        SceneError::Obj(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default)]
+    camera: CameraDescription,
+    #[serde(default)]
+    render: RenderDescription,
+    #[serde(default)]
+    background: Option<BackgroundDescription>,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDescription>,
+    objects: Vec<ObjectDescription>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    focus_dist: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RenderDescription {
+    image_width: Option<u32>,
+    image_height: Option<u32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDescription {
+    Black,
+    Solid {
+        color: [f64; 3],
+    },
+    Gradient {
+        horizon: [f64; 3],
+        zenith: [f64; 3],
+    },
+    /// An equirectangular image, relative to the scene file, rotated around the
+    /// vertical axis by `rotation` degrees.
+    Environment {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    /// A 3D checker of cubes of side `scale`.
+    Checker {
+        even: [f64; 3],
+        odd: [f64; 3],
+        #[serde(default = "default_scale")]
+        scale: f64,
+    },
+    /// A checker over the texture coordinates of the surface.
+    UvChecker {
+        even: [f64; 3],
+        odd: [f64; 3],
+        columns: u32,
+        rows: u32,
+    },
+    /// A PNG or JPEG file, relative to the scene file.
+    Image {
+        path: PathBuf,
+    },
+    Noise {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+        base: Option<[f64; 3]>,
+        vein: Option<[f64; 3]>,
+    },
+    Wood {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+        light: Option<[f64; 3]>,
+        dark: Option<[f64; 3]>,
+    },
+}
+
+/// A constant color or the name of a texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorOrTexture {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: ColorOrTexture,
+    },
+    Metal {
+        albedo: ColorOrTexture,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ir: f64,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: String,
+    },
+    /// A Wavefront OBJ file. `material` is used for faces without a `usemtl` statement.
+    Mesh { path: PathBuf, material: String },
+}
+
+fn vec3([x, y, z]: [f64; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+fn is_finite(v: [f64; 3]) -> bool {
+    v.iter().all(|c| c.is_finite())
+}
+
+fn color(name: &str, color: [f64; 3]) -> Result<Vec3, String> {
+    if color.iter().all(|c| c.is_finite() && *c >= 0.0) {
+        Ok(vec3(color))
+    } else {
+        Err(format!(
+            "{} components must be finite and not negative",
+            name
+        ))
+    }
+}
+
+/// Lists the names of `map` for error messages.
+fn known_names<T>(map: &BTreeMap<String, T>) -> String {
+    if map.is_empty() {
+        "none".to_string()
+    } else {
+        map.keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl SceneDescription {
+    fn build(self, path: &Path) -> Result<Scene, SceneError> {
+        let invalid = |message: String| SceneError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let camera = self.camera.build().map_err(invalid)?;
+        let settings = self.render.build().map_err(invalid)?;
+        let background = match self.background {
+            Some(background) => background.build(path)?,
+            None => Background::sky(),
+        };
+
+        let mut textures: BTreeMap<String, Arc<dyn Texture>> = BTreeMap::new();
+        for (name, texture) in self.textures {
+            let texture = texture.build(path, &name)?;
+            textures.insert(name, texture);
+        }
+
+        let mut materials: BTreeMap<String, Arc<dyn Material + Send + Sync>> = BTreeMap::new();
+        for (name, material) in self.materials {
+            let material = material
+                .build(&textures)
+                .map_err(|message| invalid(format!("materials.{}: {}", name, message)))?;
+            materials.insert(name, material);
+        }
+
+        let material = |index: usize, name: &str| {
+            materials.get(name).cloned().ok_or_else(|| {
+                invalid(format!(
+                    "objects[{}]: unknown material '{}' (known materials: {})",
+                    index,
+                    name,
+                    known_names(&materials)
+                ))
+            })
+        };
+
+        let mut world = HittableList::new();
+        for (index, object) in self.objects.into_iter().enumerate() {
+            let object_error = |message: &str| invalid(format!("objects[{}]: {}", index, message));
+
+            match object {
+                ObjectDescription::Sphere {
+                    center,
+                    radius,
+                    material: name,
+                } => {
+                    if !is_finite(center) {
+                        return Err(object_error("sphere center must be finite"));
+                    }
+                    // A negative radius is allowed, it flips the normals for hollow glass.
+                    if radius == 0.0 || !radius.is_finite() {
+                        return Err(object_error("sphere radius must be finite and non-zero"));
+                    }
+
+                    world.add(Arc::new(Sphere::new(
+                        vec3(center),
+                        radius,
+                        material(index, &name)?,
+                    )));
+                }
+                ObjectDescription::Triangle {
+                    vertices: [v0, v1, v2],
+                    normals,
+                    uvs,
+                    material: name,
+                } => {
+                    if ![v0, v1, v2].into_iter().all(is_finite) {
+                        return Err(object_error("triangle vertices must be finite"));
+                    }
+
+                    let mut triangle =
+                        Triangle::new(vec3(v0), vec3(v1), vec3(v2), material(index, &name)?);
+                    if let Some(normals) = normals {
+                        triangle = triangle.with_normals(normals.map(vec3));
+                    }
+                    if let Some(uvs) = uvs {
+                        triangle = triangle.with_uvs(uvs.map(|[u, v]| (u, v)));
+                    }
+
+                    world.add(Arc::new(triangle));
+                }
+                ObjectDescription::Mesh {
+                    path: mesh_path,
+                    material: name,
+                } => {
+                    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                    let meshes = obj::load_obj(directory.join(mesh_path), material(index, &name)?)?;
+
+                    for mesh in meshes.objects {
+                        world.add(mesh);
+                    }
+                }
+            }
+        }
+
+        if world.objects.is_empty() {
+            return Err(invalid("the scene has no objects".to_string()));
+        }
+
+        Ok(Scene {
+            world,
+            camera,
+            settings,
+            background,
+        })
+    }
+}
+
+impl CameraDescription {
+    fn build(self) -> Result<CameraSettings, String> {
+        let defaults = CameraSettings::default();
+
+        let camera = CameraSettings {
+            lookfrom: self.lookfrom.map_or(defaults.lookfrom, vec3),
+            lookat: self.lookat.map_or(defaults.lookat, vec3),
+            vup: self.vup.map_or(defaults.vup, vec3),
+            vfov: self.vfov.unwrap_or(defaults.vfov),
+            aperture: self.aperture.unwrap_or(defaults.aperture),
+            focus_dist: self.focus_dist.unwrap_or(defaults.focus_dist),
+        };
+
+        validate_camera(&camera).map(|_| camera)
+    }
+}
+
+/// Checks that `camera` describes a usable view, shared with command-line overrides.
+pub fn validate_camera(camera: &CameraSettings) -> Result<(), String> {
+    let finite = |v: Vec3| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+
+    if !finite(camera.lookfrom) || !finite(camera.lookat) || !finite(camera.vup) {
+        return Err("camera: lookfrom, lookat and vup must be finite".to_string());
+    }
+
+    let view = camera.lookfrom - camera.lookat;
+    if view.near_zero() {
+        return Err("camera: lookfrom and lookat must be different points".to_string());
+    }
+    if camera.vup.cross(view).near_zero() {
+        return Err("camera: vup must not be parallel to the viewing direction".to_string());
+    }
+    if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+        return Err(format!(
+            "camera: vfov must be between 0 and 180 degrees, got {}",
+            camera.vfov
+        ));
+    }
+    if !(camera.aperture >= 0.0 && camera.aperture.is_finite()) {
+        return Err(format!(
+            "camera: aperture must not be negative, got {}",
+            camera.aperture
+        ));
+    }
+    if !(camera.focus_dist > 0.0 && camera.focus_dist.is_finite()) {
+        return Err(format!(
+            "camera: focus_dist must be positive, got {}",
+            camera.focus_dist
+        ));
+    }
+
+    Ok(())
+}
+
+impl RenderDescription {
+    fn build(self) -> Result<RenderSettings, String> {
+        let mut settings = RenderSettings::default();
+
+        settings
+            .set_image_size(
+                self.image_width.unwrap_or(settings.image_width),
+                self.image_height,
+                self.aspect_ratio,
+            )
+            .map_err(|message| format!("render: {}", message))?;
+        settings.samples_per_pixel = self.samples_per_pixel.unwrap_or(settings.samples_per_pixel);
+        settings.max_depth = self.max_depth.unwrap_or(settings.max_depth);
+
+        validate_settings(&settings).map(|_| settings)
+    }
+}
+
+/// Checks that `settings` can be rendered, shared with command-line overrides.
+pub fn validate_settings(settings: &RenderSettings) -> Result<(), String> {
+    if settings.image_width == 0 || settings.image_height == 0 {
+        return Err("render: image_width and image_height must be positive".to_string());
+    }
+    if settings.samples_per_pixel == 0 {
+        return Err("render: samples_per_pixel must be positive".to_string());
+    }
+    if settings.max_depth < 1 {
+        return Err(format!(
+            "render: max_depth must be at least 1, got {}",
+            settings.max_depth
+        ));
+    }
+
+    Ok(())
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+impl BackgroundDescription {
+    fn build(self, path: &Path) -> Result<Background, SceneError> {
+        let invalid = |message: String| SceneError::Invalid {
+            path: path.to_path_buf(),
+            message: format!("background: {}", message),
+        };
+        let color = |name: &str, c: [f64; 3]| color(name, c).map_err(invalid);
+
+        Ok(match self {
+            BackgroundDescription::Black => Background::Black,
+            BackgroundDescription::Solid { color: c } => Background::Solid(color("color", c)?),
+            BackgroundDescription::Gradient { horizon, zenith } => Background::Gradient {
+                horizon: color("horizon", horizon)?,
+                zenith: color("zenith", zenith)?,
+            },
+            BackgroundDescription::Environment {
+                path: map_path,
+                rotation,
+                intensity,
+            } => {
+                if !rotation.is_finite() {
+                    return Err(invalid(format!(
+                        "rotation must be finite, got {}",
+                        rotation
+                    )));
+                }
+                if !(intensity.is_finite() && intensity >= 0.0) {
+                    return Err(invalid(format!(
+                        "intensity must not be negative, got {}",
+                        intensity
+                    )));
+                }
+
+                let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                let map_path = directory.join(map_path);
+
+                let map = EnvironmentMap::load(&map_path).map_err(|source| SceneError::Image {
+                    path: map_path,
+                    source,
+                })?;
+                Background::Environment(Arc::new(
+                    map.with_rotation(rotation).with_intensity(intensity),
+                ))
+            }
+        })
+    }
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl TextureDescription {
+    fn build(self, path: &Path, name: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        let invalid = |message: String| SceneError::Invalid {
+            path: path.to_path_buf(),
+            message: format!("textures.{}: {}", name, message),
+        };
+        let solid = |name: &str, c: [f64; 3]| -> Result<Arc<dyn Texture>, SceneError> {
+            Ok(Arc::new(SolidColor::new(color(name, c).map_err(invalid)?)))
+        };
+
+        let positive = |scale: f64| {
+            if scale > 0.0 && scale.is_finite() {
+                Ok(scale)
+            } else {
+                Err(invalid(format!("scale must be positive, got {}", scale)))
+            }
+        };
+        // Both colors or neither, falling back to the defaults of the texture.
+        let colors = |names: [&str; 2], colors: [Option<[f64; 3]>; 2]| match colors {
+            [Some(a), Some(b)] => Ok(Some((
+                color(names[0], a).map_err(invalid)?,
+                color(names[1], b).map_err(invalid)?,
+            ))),
+            [None, None] => Ok(None),
+            _ => Err(invalid(format!(
+                "{} and {} must be given together",
+                names[0], names[1]
+            ))),
+        };
+
+        Ok(match self {
+            TextureDescription::Solid { color } => solid("color", color)?,
+            TextureDescription::Checker { even, odd, scale } => {
+                let scale = positive(scale)?;
+                Arc::new(CheckerTexture::new(
+                    scale,
+                    solid("even", even)?,
+                    solid("odd", odd)?,
+                ))
+            }
+            TextureDescription::UvChecker {
+                even,
+                odd,
+                columns,
+                rows,
+            } => {
+                if columns == 0 || rows == 0 {
+                    return Err(invalid("columns and rows must be positive".to_string()));
+                }
+                Arc::new(CheckerTexture::uv(
+                    columns,
+                    rows,
+                    solid("even", even)?,
+                    solid("odd", odd)?,
+                ))
+            }
+            TextureDescription::Image { path: image_path } => {
+                let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                let image_path = directory.join(image_path);
+
+                let texture =
+                    ImageTexture::load(&image_path).map_err(|source| SceneError::Image {
+                        path: image_path,
+                        source,
+                    })?;
+                Arc::new(texture)
+            }
+            TextureDescription::Noise { scale, seed } => {
+                Arc::new(NoiseTexture::new(seed, positive(scale)?))
+            }
+            TextureDescription::Marble {
+                scale,
+                seed,
+                base,
+                vein,
+            } => {
+                let mut marble = MarbleTexture::new(seed, positive(scale)?);
+                if let Some((base, vein)) = colors(["base", "vein"], [base, vein])? {
+                    marble = marble.with_colors(base, vein);
+                }
+                Arc::new(marble)
+            }
+            TextureDescription::Wood {
+                scale,
+                seed,
+                light,
+                dark,
+            } => {
+                let mut wood = WoodTexture::new(seed, positive(scale)?);
+                if let Some((light, dark)) = colors(["light", "dark"], [light, dark])? {
+                    wood = wood.with_colors(light, dark);
+                }
+                Arc::new(wood)
+            }
+        })
+    }
+}
+
+impl ColorOrTexture {
+    fn build(
+        self,
+        name: &str,
+        textures: &BTreeMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, String> {
+        match self {
+            ColorOrTexture::Color(c) => Ok(Arc::new(SolidColor::new(color(name, c)?))),
+            ColorOrTexture::Texture(texture) => textures.get(&texture).cloned().ok_or_else(|| {
+                format!(
+                    "unknown texture '{}' (known textures: {})",
+                    texture,
+                    known_names(textures)
+                )
+            }),
+        }
+    }
+}
+
+impl MaterialDescription {
+    fn build(
+        self,
+        textures: &BTreeMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Material + Send + Sync>, String> {
+        Ok(match self {
+            MaterialDescription::Lambertian { albedo } => {
+                Arc::new(Lambertian::with_texture(albedo.build("albedo", textures)?))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                if !(0.0..=1.0).contains(&fuzz) {
+                    return Err(format!("fuzz must be between 0 and 1, got {}", fuzz));
+                }
+                Arc::new(Metal::with_texture(albedo.build("albedo", textures)?, fuzz))
+            }
+            MaterialDescription::Dielectric { ir } => {
+                if !(ir > 0.0 && ir.is_finite()) {
+                    return Err(format!("ir must be positive, got {}", ir));
+                }
+                Arc::new(Dielectric::new(ir))
+            }
+            MaterialDescription::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new(color("emit", emit)?))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+
+    use rand::prelude::*;
+
+    fn parse_error(source: &str) -> String {
+        Scene::from_json(source, Path::new("test.json"))
+            .err()
+            .expect("expected an invalid scene")
+            .to_string()
+    }
+
+    #[test]
+    fn scene_from_json() {
+        let scene = Scene::from_json(
+            r#"{
+                "camera": { "lookfrom": [0, 0, 5], "vfov": 40 },
+                "render": { "image_width": 64, "samples_per_pixel": 2 },
+                "materials": {
+                    "red": { "type": "lambertian", "albedo": [0.8, 0.1, 0.1] },
+                    "mirror": { "type": "metal", "albedo": [0.9, 0.9, 0.9] }
+                },
+                "objects": [
+                    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "red" },
+                    {
+                        "type": "triangle",
+                        "vertices": [[-2, -1, -2], [2, -1, -2], [0, -1, 2]],
+                        "material": "mirror"
+                    }
+                ]
+            }"#,
+            Path::new("test.json"),
+        )
+        .unwrap();
+
+        assert_eq!(scene.world.objects.len(), 2);
+        assert_eq!(scene.camera.lookfrom, Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(scene.camera.vfov, 40.0);
+        assert_eq!(scene.camera.aperture, CameraSettings::default().aperture);
+        assert_eq!(scene.settings.image_width, 64);
+        assert_eq!(scene.settings.image_height, 42);
+        assert_eq!(scene.settings.samples_per_pixel, 2);
+        assert_eq!(
+            scene.settings.max_depth,
+            RenderSettings::default().max_depth
+        );
+        assert!(matches!(scene.background, Background::Gradient { .. }));
+
+        let scene = Scene::from_json(
+            r#"{
+                "textures": {
+                    "tiles": { "type": "uv_checker", "even": [1, 1, 1], "odd": [0, 0, 0], "columns": 2, "rows": 1 }
+                },
+                "materials": { "tiled": { "type": "lambertian", "albedo": "tiles" } },
+                "objects": [
+                    {
+                        "type": "triangle",
+                        "vertices": [[0, 0, 0], [1, 0, 0], [0, 1, 0]],
+                        "uvs": [[0, 0], [1, 0], [0, 1]],
+                        "material": "tiled"
+                    }
+                ]
+            }"#,
+            Path::new("test.json"),
+        )
+        .unwrap();
+        for (x, expected) in [(0.25, 1.0), (0.75, 0.0)] {
+            let r = Ray::new(Vec3::new(x, 0.1, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = scene.world.hit(r, 0.001, f64::INFINITY).unwrap();
+
+            let srec = rec
+                .material
+                .scatter(&r, &rec, &mut StdRng::seed_from_u64(0))
+                .unwrap();
+            assert_eq!(srec.attenuation, Vec3::new(expected, expected, expected));
+        }
+
+        let scene = Scene::from_json(
+            r#"{
+                "background": { "type": "solid", "color": [0.1, 0.2, 0.3] },
+                "materials": { "white": { "type": "lambertian", "albedo": [1, 1, 1] } },
+                "objects": [
+                    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "white" }
+                ]
+            }"#,
+            Path::new("test.json"),
+        )
+        .unwrap();
+        assert!(matches!(scene.background, Background::Solid(c) if c == Vec3::new(0.1, 0.2, 0.3)));
+    }
+
+    #[test]
+    fn scene_reports_invalid_references_and_values() {
+        assert_eq!(
+            parse_error(
+                r#"{
+                    "materials": { "red": { "type": "lambertian", "albedo": [1, 0, 0] } },
+                    "objects": [
+                        { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "red" },
+                        { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "blue" }
+                    ]
+                }"#
+            ),
+            "test.json: objects[1]: unknown material 'blue' (known materials: red)"
+        );
+
+        assert_eq!(
+            parse_error(
+                r#"{
+                    "materials": { "steel": { "type": "metal", "albedo": [1, 1, 1], "fuzz": 2 } },
+                    "objects": []
+                }"#
+            ),
+            "test.json: materials.steel: fuzz must be between 0 and 1, got 2"
+        );
+
+        assert_eq!(
+            parse_error(
+                r#"{
+                    "textures": { "grid": { "type": "checker", "even": [1, 1, 1], "odd": [0, 0, 0] } },
+                    "materials": { "floor": { "type": "lambertian", "albedo": "tiles" } },
+                    "objects": []
+                }"#
+            ),
+            "test.json: materials.floor: unknown texture 'tiles' (known textures: grid)"
+        );
+
+        assert_eq!(
+            parse_error(
+                r#"{
+                    "textures": { "oak": { "type": "wood", "light": [0.8, 0.6, 0.4] } },
+                    "objects": []
+                }"#
+            ),
+            "test.json: textures.oak: light and dark must be given together"
+        );
+
+        assert_eq!(
+            parse_error(r#"{ "render": { "max_depth": 0 }, "objects": [] }"#),
+            "test.json: render: max_depth must be at least 1, got 0"
+        );
+
+        assert!(
+            parse_error(r#"{ "camera": { "lookfrom": [0, 0, 0] }, "objects": [] }"#)
+                .contains("lookfrom and lookat must be different")
+        );
+    }
+
+    #[test]
+    fn scene_reports_malformed_documents() {
+        let error = parse_error("{\n  \"objects\": [\n    { \"type\": \"cube\" }\n  ]\n}");
+        assert!(error.contains("unknown variant `cube`"), "{}", error);
+        assert!(error.contains("line 3"), "{}", error);
+
+        let error = parse_error(
+            r#"{ "background": { "type": "environment", "path": "missing.hdr" }, "objects": [] }"#,
+        );
+        assert!(error.starts_with("missing.hdr: "), "{}", error);
+
+        let error = parse_error(
+            r#"{
+                "background": { "type": "environment", "path": "sky.exr", "intensity": -1 },
+                "objects": []
+            }"#,
+        );
+        assert_eq!(
+            error,
+            "test.json: background: intensity must not be negative, got -1"
+        );
+
+        let error = parse_error(r#"{ "objects": [], "lights": [] }"#);
+        assert!(error.contains("unknown field `lights`"), "{}", error);
+    }
+}
@@ -0,0 +1,253 @@
+//! Scenes that ship with the renderer.
+
+use crate::background::Background;
+use crate::camera::CameraSettings;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::sphere::Sphere;
+use crate::triangle::Triangle;
+use crate::vec3::{Color, Point3, Vec3};
+
+use rand::prelude::*;
+use std::sync::Arc;
+
+pub const BUILTIN_SCENES: &[&str] = &["random", "cornell_box"];
+
+/// Returns the built-in scene called `name` with its camera and render settings. `seed`
+/// drives everything random in the scene.
+pub fn builtin(name: &str, seed: u64) -> Option<Scene> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    match name {
+        "random" => Some(Scene {
+            world: random_scene(&mut rng),
+            camera: CameraSettings::default(),
+            settings: RenderSettings::default(),
+            background: Background::sky(),
+        }),
+        "cornell_box" => Some(Scene {
+            world: cornell_box(),
+            camera: CameraSettings {
+                lookfrom: Point3::new(278.0, 278.0, -800.0),
+                lookat: Point3::new(278.0, 278.0, 0.0),
+                vup: Vec3::new(0.0, 1.0, 0.0),
+                vfov: 40.0,
+                aperture: 0.0,
+                focus_dist: 10.0,
+            },
+            settings: RenderSettings {
+                image_width: 400,
+                image_height: 400,
+                samples_per_pixel: 100,
+                max_depth: 50,
+            },
+            background: Background::Black,
+        }),
+        _ => None,
+    }
+}
+
+fn random_color<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Color {
+    Color::new(
+        rng.gen_range(min..max),
+        rng.gen_range(min..max),
+        rng.gen_range(min..max),
+    )
+}
+
+pub fn random_scene<R: Rng + ?Sized>(rng: &mut R) -> HittableList {
+    let mut world = HittableList::new();
+
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rng.gen::<f64>();
+
+            let center = Point3::new(
+                a as f64 + 0.9 * rng.gen::<f64>(),
+                0.2,
+                b as f64 + 0.9 * rng.gen::<f64>(),
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material + Send + Sync> = if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = random_color(rng, 0.0, 1.0) * random_color(rng, 0.0, 1.0);
+                    Arc::new(Lambertian::new(albedo))
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = random_color(rng, 0.5, 1.0);
+                    let fuzz = rng.gen_range(0.0..0.5);
+                    Arc::new(Metal::new(albedo, fuzz))
+                } else {
+                    // glass
+                    Arc::new(Dielectric::new(1.5))
+                };
+
+                world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    world
+}
+
+/// Adds the parallelogram with corner `q` and edges `u` and `v` as two triangles.
+pub fn quad(
+    world: &mut HittableList,
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    material: Arc<dyn Material + Send + Sync>,
+) {
+    world.add(Arc::new(Triangle::new(
+        q,
+        q + u,
+        q + u + v,
+        material.clone(),
+    )));
+    world.add(Arc::new(Triangle::new(q, q + u + v, q + v, material)));
+}
+
+/// Adds a box spanning from the origin to `size`, rotated by `angle` degrees around the
+/// y axis and then moved by `offset`.
+pub fn rotated_box(
+    world: &mut HittableList,
+    size: Vec3,
+    angle: f64,
+    offset: Vec3,
+    material: Arc<dyn Material + Send + Sync>,
+) {
+    let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+    let rotate = |p: Vec3| {
+        Vec3::new(
+            cos_theta * p.x + sin_theta * p.z,
+            p.y,
+            -sin_theta * p.x + cos_theta * p.z,
+        )
+    };
+
+    let (dx, dy, dz) = (
+        Vec3::new(size.x, 0.0, 0.0),
+        Vec3::new(0.0, size.y, 0.0),
+        Vec3::new(0.0, 0.0, size.z),
+    );
+    let origin = Point3::default();
+
+    let faces = [
+        (origin, dx, dy),   // front
+        (dx + dz, -dx, dy), // back
+        (dz, -dz, dy),      // left
+        (dx, dz, dy),       // right
+        (dy + dz, dx, -dz), // top
+        (origin, dx, dz),   // bottom
+    ];
+
+    for (q, u, v) in faces {
+        quad(
+            world,
+            rotate(q) + offset,
+            rotate(u),
+            rotate(v),
+            material.clone(),
+        );
+    }
+}
+
+pub fn cornell_box() -> HittableList {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    quad(
+        &mut world,
+        Point3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        green,
+    );
+    quad(
+        &mut world,
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        red,
+    );
+    quad(
+        &mut world,
+        Point3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light,
+    );
+    quad(
+        &mut world,
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        white.clone(),
+    );
+    quad(
+        &mut world,
+        Point3::new(555.0, 555.0, 555.0),
+        Vec3::new(-555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -555.0),
+        white.clone(),
+    );
+    quad(
+        &mut world,
+        Point3::new(0.0, 0.0, 555.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        white.clone(),
+    );
+
+    rotated_box(
+        &mut world,
+        Vec3::new(165.0, 330.0, 165.0),
+        15.0,
+        Vec3::new(265.0, 0.0, 295.0),
+        white.clone(),
+    );
+    rotated_box(
+        &mut world,
+        Vec3::new(165.0, 165.0, 165.0),
+        -18.0,
+        Vec3::new(130.0, 0.0, 65.0),
+        white,
+    );
+
+    world
+}
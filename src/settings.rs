@@ -0,0 +1,63 @@
+/// Image and sampling parameters of a render.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        let image_width = 400;
+
+        Self {
+            image_width,
+            image_height: Self::height_for(image_width, 3.0 / 2.0),
+            samples_per_pixel: 5,
+            max_depth: 2,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The height of an image `image_width` pixels wide with the given aspect ratio.
+    pub fn height_for(image_width: u32, aspect_ratio: f64) -> u32 {
+        ((image_width as f64 / aspect_ratio) as u32).max(1)
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+
+    /// Sets the image size from a width and at most one of a height or an aspect ratio.
+    /// With neither, the current aspect ratio is kept.
+    pub fn set_image_size(
+        &mut self,
+        image_width: u32,
+        image_height: Option<u32>,
+        aspect_ratio: Option<f64>,
+    ) -> Result<(), String> {
+        let image_height = match (image_height, aspect_ratio) {
+            (Some(_), Some(_)) => {
+                return Err("give either an image height or an aspect ratio, not both".to_string())
+            }
+            (Some(image_height), None) => image_height,
+            (None, Some(aspect_ratio)) => {
+                if !(aspect_ratio > 0.0 && aspect_ratio.is_finite()) {
+                    return Err(format!(
+                        "aspect ratio must be positive, got {}",
+                        aspect_ratio
+                    ));
+                }
+                Self::height_for(image_width, aspect_ratio)
+            }
+            (None, None) => Self::height_for(image_width, self.aspect_ratio()),
+        };
+
+        self.image_width = image_width;
+        self.image_height = image_height;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,13 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::vec3::Point3;
+use crate::texture;
+use crate::vec3::{Point3, Vec3};
 
+use rand::prelude::*;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 pub struct Sphere {
@@ -22,7 +27,7 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.origin - self.center;
         let a = r.direction.length_squared();
         let half_b = oc.dot(r.direction);
@@ -31,7 +36,7 @@ impl Hittable for Sphere {
         let discriminant = half_b * half_b - a * c;
 
         if discriminant < 0.0 {
-            return false;
+            return None;
         }
 
         let sqrtd = discriminant.sqrt();
@@ -42,16 +47,77 @@ impl Hittable for Sphere {
         if root < t_min || t_max < root {
             root = (-half_b + sqrtd) / a;
             if root < t_min || t_max < root {
-                return false;
+                return None;
             }
         }
 
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        rec.material = self.material.clone();
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
+        let outward_normal = (r.at(root) - self.center) / self.radius;
 
-        true
+        Some(HitRecord::new(
+            r,
+            root,
+            outward_normal,
+            texture::direction_to_uv(outward_normal),
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Hollow spheres have a negative radius, but the same extent.
+        let radius = self.radius.abs();
+        let radius = Vec3::new(radius, radius, radius);
+
+        Some(Aabb::new(self.center - radius, self.center + radius))
+    }
+
+    /// Samples the cone of directions the sphere covers from `origin`.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self
+            .hit(Ray::new(origin, direction), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let radius_squared = self.radius * self.radius;
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= radius_squared {
+            // From inside, the sphere covers every direction.
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        let direction = self.center - origin;
+        let radius_squared = self.radius * self.radius;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= radius_squared {
+            return Vec3::random_unit_vector(rng);
+        }
+
+        let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+
+        let z = 1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        Onb::build_from_w(direction).local(Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            z,
+        ))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        f(self.material.as_ref())
     }
 }
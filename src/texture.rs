@@ -0,0 +1,334 @@
+use crate::perlin::Perlin;
+use crate::vec3::{Color, Point3, Vec3};
+
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A color that varies over a surface, looked up by texture coordinates and hit point.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.color
+    }
+}
+
+/// Alternates between two textures in a checkerboard pattern.
+pub struct CheckerTexture {
+    pattern: CheckerPattern,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+enum CheckerPattern {
+    /// Cubes of side `scale` filling space, independent of the surface parametrization.
+    Solid { scale: f64 },
+    /// A grid of `columns` by `rows` squares over the texture coordinates.
+    Uv { columns: f64, rows: f64 },
+}
+
+impl CheckerTexture {
+    /// A 3D checker made of cubes of side `scale`.
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            pattern: CheckerPattern::Solid { scale },
+            even,
+            odd,
+        }
+    }
+
+    /// A checker over the `[0, 1]` texture coordinates, `columns` squares along `u` and
+    /// `rows` along `v`.
+    pub fn uv(columns: u32, rows: u32, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            pattern: CheckerPattern::Uv {
+                columns: columns as f64,
+                rows: rows as f64,
+            },
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let cell = match self.pattern {
+            CheckerPattern::Solid { scale } => {
+                (p.x / scale).floor() + (p.y / scale).floor() + (p.z / scale).floor()
+            }
+            CheckerPattern::Uv { columns, rows } => (u * columns).floor() + (v * rows).floor(),
+        };
+
+        if cell.rem_euclid(2.0) < 1.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// An image mapped over the `[0, 1]` texture coordinates, `v` going up from the bottom
+/// row. Coordinates outside that range are clamped to the border.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Panics if `pixels` does not hold `width * height` linear colors.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "Image texture without pixels");
+        assert_eq!(pixels.len(), width * height, "One color per pixel");
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads a PNG, JPEG or any other 8 bit format the `image` crate can decode. The
+    /// pixels are taken to be sRGB encoded and converted to linear colors.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgb8();
+
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                Color::new(
+                    srgb_to_linear(p[0]),
+                    srgb_to_linear(p[1]),
+                    srgb_to_linear(p[2]),
+                )
+            })
+            .collect();
+
+        Ok(Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            pixels,
+        ))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Gray Perlin noise, `scale` being the frequency of the noise.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let t = 0.5 * (1.0 + self.noise.noise(self.scale * p));
+
+        Color::new(t, t, t)
+    }
+}
+
+/// Veins running across the z axis, bent by turbulence. `scale` is the frequency of the
+/// veins.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    base: Color,
+    vein: Color,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            base: Color::new(0.9, 0.9, 0.9),
+            vein: Color::new(0.1, 0.1, 0.1),
+        }
+    }
+
+    pub fn with_colors(mut self, base: Color, vein: Color) -> Self {
+        self.base = base;
+        self.vein = vein;
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let phase = self.scale * p.z + 10.0 * self.noise.turbulence(p, 7);
+        let t = 0.5 * (1.0 + phase.sin());
+
+        t * self.base + (1.0 - t) * self.vein
+    }
+}
+
+/// Growth rings around the y axis, distorted by fractional Brownian motion. `scale` is
+/// the number of rings per unit of distance from the axis.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            light: Color::new(0.75, 0.55, 0.33),
+            dark: Color::new(0.42, 0.26, 0.13),
+        }
+    }
+
+    pub fn with_colors(mut self, light: Color, dark: Color) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = self.scale * radius + 0.5 * self.noise.fbm(p, 4);
+
+        // A triangle wave, so the rings blend into each other on both sides.
+        let t = (2.0 * rings.rem_euclid(1.0) - 1.0).abs();
+
+        t * self.light + (1.0 - t) * self.dark
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Maps a unit direction onto `[0, 1]` texture coordinates: `u` goes around the y axis
+/// starting from -x, `v` goes up from the -y pole.
+pub fn direction_to_uv(d: Vec3) -> (f64, f64) {
+    let theta = (-d.y).clamp(-1.0, 1.0).acos();
+    let phi = (-d.z).atan2(d.x) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checker_textures() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let black = Color::default();
+
+        let solid = CheckerTexture::from_colors(2.0, white, black);
+        assert_eq!(solid.value(0.0, 0.0, Point3::new(0.5, 0.5, 0.5)), white);
+        assert_eq!(solid.value(0.0, 0.0, Point3::new(2.5, 0.5, 0.5)), black);
+        assert_eq!(solid.value(0.0, 0.0, Point3::new(-0.5, 0.5, 0.5)), black);
+        assert_eq!(solid.value(0.0, 0.0, Point3::new(-2.5, 0.5, 0.5)), white);
+
+        let uv = CheckerTexture::uv(
+            4,
+            2,
+            Arc::new(SolidColor::new(white)),
+            Arc::new(SolidColor::new(black)),
+        );
+        assert_eq!(uv.value(0.1, 0.1, Point3::default()), white);
+        assert_eq!(uv.value(0.3, 0.1, Point3::default()), black);
+        assert_eq!(uv.value(0.3, 0.6, Point3::default()), white);
+    }
+
+    #[test]
+    fn image_texture_lookup() {
+        let red = Color::new(1.0, 0.0, 0.0);
+        let green = Color::new(0.0, 1.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let texture = ImageTexture::new(2, 2, vec![red, green, blue, blue]);
+
+        // The first row is the top of the image.
+        assert_eq!(texture.value(0.25, 0.75, Point3::default()), red);
+        assert_eq!(texture.value(0.75, 0.75, Point3::default()), green);
+        assert_eq!(texture.value(0.25, 0.25, Point3::default()), blue);
+        assert_eq!(texture.value(2.0, 1.5, Point3::default()), green);
+
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        assert!((srgb_to_linear(188) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn procedural_textures_stay_between_their_colors() {
+        let noise = NoiseTexture::new(4, 3.0);
+        let marble = MarbleTexture::new(4, 2.0);
+        let wood = WoodTexture::new(4, 4.0);
+
+        for i in 0..500 {
+            let p = Point3::new(i as f64 * 0.37, (i % 17) as f64 * 0.21, i as f64 * -0.13);
+
+            let gray = noise.value(0.0, 0.0, p);
+            assert!((0.0..=1.0).contains(&gray.x), "{:?}", gray);
+
+            let marble = marble.value(0.0, 0.0, p);
+            assert!((0.1..=0.9).contains(&marble.x), "{:?}", marble);
+
+            let wood = wood.value(0.0, 0.0, p);
+            assert!((0.42..=0.75).contains(&wood.x), "{:?}", wood);
+        }
+
+        // The same seed gives the same texture.
+        let p = Point3::new(1.3, 2.1, -0.4);
+        assert_eq!(
+            WoodTexture::new(9, 4.0).value(0.0, 0.0, p),
+            WoodTexture::new(9, 4.0).value(0.0, 0.0, p)
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::color::{format_pixel_color, luminance};
+use crate::vec3::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapper {
+    #[default]
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    Agx,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMapper,
+    pub exposure: f64,
+    pub white_point: f64,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapper::default(),
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMapper {
+    pub const NAMES: [&'static str; 6] = [
+        "clamp",
+        "reinhard",
+        "reinhard-extended",
+        "aces",
+        "hable",
+        "agx",
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clamp" => Some(ToneMapper::Clamp),
+            "reinhard" => Some(ToneMapper::Reinhard),
+            "reinhard-extended" => Some(ToneMapper::ExtendedReinhard),
+            "aces" => Some(ToneMapper::Aces),
+            "hable" => Some(ToneMapper::Hable),
+            "agx" => Some(ToneMapper::Agx),
+            _ => None,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn map(&self, color: Color) -> Color {
+        let color = 2f64.powf(self.exposure) * color;
+
+        let mapped = match self.operator {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapper::ExtendedReinhard => {
+                let white = self.white_point * self.white_point;
+                scale_luminance(color, |l| l * (1.0 + l / white) / (1.0 + l))
+            }
+            ToneMapper::Aces => per_channel(color, aces),
+            ToneMapper::Hable => {
+                let white = hable(HABLE_WHITE);
+                per_channel(color, |x| hable(HABLE_EXPOSURE_BIAS * x) / white)
+            }
+            ToneMapper::Agx => agx(color),
+        };
+
+        per_channel(mapped, |x| x.clamp(0.0, 1.0))
+    }
+
+    pub fn to_rgb8(&self, color: Color) -> (u8, u8, u8) {
+        format_pixel_color(self.map(color), 1)
+    }
+}
+
+fn per_channel(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(color.x), f(color.y), f(color.z))
+}
+
+fn scale_luminance(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(color);
+
+    if l > 0.0 {
+        f(l) / l * color
+    } else {
+        Color::default()
+    }
+}
+
+fn aces(x: f64) -> f64 {
+    // The fit expects the exposure of the reference transform, 0.6 times ours.
+    let x = 0.6 * x;
+
+    x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+const HABLE_EXPOSURE_BIAS: f64 = 2.0;
+const HABLE_WHITE: f64 = 11.2;
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+
+const AGX_INSET: [[f64; 3]; 3] = [
+    [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+    [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+    [0.0423756549057051, 0.0784336, 0.879142973793104],
+];
+
+const AGX_OUTSET: [[f64; 3]; 3] = [
+    [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+    [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+    [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+];
+
+fn agx(color: Color) -> Color {
+    let color = transform(AGX_INSET, color);
+
+    let encoded = per_channel(color, |x| {
+        let ev = x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV);
+        agx_contrast((ev - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV))
+    });
+
+    // The sigmoid gives display encoded values, back to linear for the sRGB encoding.
+    per_channel(transform(AGX_OUTSET, encoded), |x| x.max(0.0).powf(2.2))
+}
+
+fn agx_contrast(x: f64) -> f64 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
+
+fn transform(matrix: [[f64; 3]; 3], color: Color) -> Color {
+    let row = |r: [f64; 3]| r[0] * color.x + r[1] * color.y + r[2] * color.z;
+
+    Color::new(row(matrix[0]), row(matrix[1]), row(matrix[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::linear_to_srgb;
+
+    fn tone_mapping(operator: ToneMapper) -> ToneMapping {
+        ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        }
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_displayable() {
+        for name in ToneMapper::NAMES {
+            let mapping = tone_mapping(ToneMapper::from_name(name).unwrap());
+            let grey = |x: f64| mapping.map(Color::new(x, x, x));
+
+            assert!(grey(0.0).x.abs() < 1e-3, "{}", name);
+
+            let mut previous = grey(0.0);
+            for i in 1..200 {
+                let mapped = grey(0.05 * i as f64 * i as f64);
+                for channel in [mapped.x, mapped.y, mapped.z] {
+                    assert!((0.0..=1.0).contains(&channel), "{}", name);
+                }
+                assert!(mapped.y >= previous.y, "{}", name);
+                previous = mapped;
+            }
+
+            let saturated = mapping.map(Color::new(50.0, 1.0, 0.0));
+            assert!(
+                saturated.x >= saturated.y && saturated.y >= saturated.z,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn operators_roll_off_highlights() {
+        let grey = |operator, x: f64| tone_mapping(operator).map(Color::new(x, x, x)).x;
+
+        assert_eq!(grey(ToneMapper::Clamp, 0.25), 0.25);
+        assert_eq!(grey(ToneMapper::Clamp, 3.0), 1.0);
+        assert!((grey(ToneMapper::Reinhard, 1.0) - 0.5).abs() < 1e-12);
+        assert!(grey(ToneMapper::Reinhard, 1000.0) < 1.0);
+        assert!((grey(ToneMapper::ExtendedReinhard, 4.0) - 1.0).abs() < 1e-12);
+        let hable_white = HABLE_WHITE / HABLE_EXPOSURE_BIAS;
+        assert!((grey(ToneMapper::Hable, hable_white) - 1.0).abs() < 1e-12);
+        assert!(grey(ToneMapper::Aces, 100.0) > 0.99);
+        assert!(grey(ToneMapper::Agx, 100.0) > 0.95);
+
+        // The filmic curves lift the shadows less than they compress the highlights.
+        for operator in [ToneMapper::Aces, ToneMapper::Hable, ToneMapper::Agx] {
+            let middle_grey = grey(operator, 0.18);
+            assert!(0.05 < middle_grey && middle_grey < 0.3, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let mapping = ToneMapping {
+            exposure: 2.0,
+            ..ToneMapping::default()
+        };
+        assert_eq!(
+            mapping.map(Color::new(0.1, 0.2, 0.0)),
+            Color::new(0.4, 0.8, 0.0)
+        );
+
+        let mapping = ToneMapping {
+            exposure: -1.0,
+            ..ToneMapping::default()
+        };
+        assert_eq!(
+            mapping.map(Color::new(1.5, 0.5, 4.0)),
+            Color::new(0.75, 0.25, 1.0)
+        );
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-12);
+        assert!((linear_to_srgb(0.5) - 0.735357).abs() < 1e-6);
+        assert!((linear_to_srgb(0.0031308) - linear_to_srgb(0.0031309)).abs() < 1e-5);
+
+        let mapping = ToneMapping::default();
+        assert_eq!(mapping.to_rgb8(Color::new(0.0, 0.18, 2.0)), (0, 118, 255));
+    }
+}
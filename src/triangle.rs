@@ -0,0 +1,318 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
+use std::sync::Arc;
+
+/// A single triangle. Vertices are expected in counter-clockwise order when looking at
+/// the front face.
+///
+/// Without per-vertex UVs the texture coordinates reported in `HitRecord` are the
+/// barycentric weights `(b1, b2)` of the second and third vertices.
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Triangle {
+    pub fn new(
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+///
+/// Returns the ray parameter and the barycentric weights `(b1, b2)` of `v1` and `v2`.
+pub(crate) fn intersect(
+    [v0, v1, v2]: [Point3; 3],
+    r: Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    let pvec = r.direction.cross(edge2);
+    let det = edge1.dot(pvec);
+
+    if det.abs() < 1e-12 {
+        // The ray is parallel to the triangle plane.
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = r.direction.dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Bounds of a triangle, padded so axis-aligned triangles still have some thickness.
+pub(crate) fn bounds([v0, v1, v2]: [Point3; 3]) -> Aabb {
+    let delta = 1e-4;
+    let padding = Vec3::new(delta, delta, delta);
+
+    let bbox = Aabb::surrounding_box(Aabb::new(v0, v0), Aabb::new(v1, v1));
+    let bbox = Aabb::surrounding_box(bbox, Aabb::new(v2, v2));
+
+    Aabb::new(bbox.minimum - padding, bbox.maximum + padding)
+}
+
+pub(crate) fn area([v0, v1, v2]: [Point3; 3]) -> f64 {
+    0.5 * (v1 - v0).cross(v2 - v0).length()
+}
+
+/// A point picked uniformly over the triangle.
+pub(crate) fn sample_point<R: Rng + ?Sized>([v0, v1, v2]: [Point3; 3], rng: &mut R) -> Point3 {
+    let r1 = rng.gen::<f64>().sqrt();
+    let r2 = rng.gen::<f64>();
+
+    (1.0 - r1) * v0 + r1 * (1.0 - r2) * v1 + r1 * r2 * v2
+}
+
+/// Converts a density of `1 / area` over a surface to a density per unit solid angle, for
+/// the direction `direction` reaching `vertices` at ray parameter `t`. Both sides of the
+/// triangle count as facing the origin.
+pub(crate) fn solid_angle_pdf(
+    [v0, v1, v2]: [Point3; 3],
+    direction: Vec3,
+    t: f64,
+    area: f64,
+) -> f64 {
+    let normal = (v1 - v0).cross(v2 - v0).unit_vector();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (direction.dot(normal) / direction.length()).abs();
+
+    if cosine <= 0.0 || area <= 0.0 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * area)
+}
+
+pub(crate) fn interpolate<T>(values: [T; 3], b1: f64, b2: f64) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f64, Output = T>,
+{
+    values[0] * (1.0 - b1 - b2) + values[1] * b1 + values[2] * b2
+}
+
+/// The record of a hit at barycentric `(b1, b2)`, with the normal bent towards the
+/// interpolated vertex normals if there are any.
+pub(crate) fn hit_record<'a>(
+    r: Ray,
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    (t, b1, b2): (f64, f64, f64),
+    material: &'a dyn Material,
+) -> HitRecord<'a> {
+    let [v0, v1, v2] = vertices;
+
+    let uv = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            interpolate([uv0.0, uv1.0, uv2.0], b1, b2),
+            interpolate([uv0.1, uv1.1, uv2.1], b1, b2),
+        ),
+        None => (b1, b2),
+    };
+
+    let geometric_normal = (v1 - v0).cross(v2 - v0).unit_vector();
+    let mut rec = HitRecord::new(r, t, geometric_normal, uv, material);
+
+    if let Some(normals) = normals {
+        let shading_normal = interpolate(normals, b1, b2);
+
+        if !shading_normal.near_zero() {
+            let shading_normal = shading_normal.unit_vector();
+
+            // Keep the shading normal on the side the ray came from.
+            rec.normal = if shading_normal.dot(rec.normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+    }
+
+    rec
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = intersect(self.vertices, r, t_min, t_max)?;
+
+        Some(hit_record(
+            r,
+            self.vertices,
+            self.normals,
+            self.uvs,
+            hit,
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounds(self.vertices))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        match intersect(
+            self.vertices,
+            Ray::new(origin, direction),
+            0.001,
+            f64::INFINITY,
+        ) {
+            Some((t, _, _)) => solid_angle_pdf(self.vertices, direction, t, area(self.vertices)),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        sample_point(self.vertices, rng) - origin
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn for_each_material(&self, f: &mut dyn FnMut(&dyn Material)) {
+        f(self.material.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::default()),
+        )
+    }
+
+    #[test]
+    fn triangle_hit_barycentric() {
+        let triangle = unit_triangle();
+        let r = Ray::new(Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let rec = triangle.hit(r, 0.001, f64::INFINITY).unwrap();
+
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.p, Point3::new(0.25, 0.5, 0.0));
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn triangle_miss() {
+        let triangle = unit_triangle();
+
+        let outside = Ray::new(Point3::new(0.75, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let parallel = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        let behind = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(triangle.hit(outside, 0.001, f64::INFINITY).is_none());
+        assert!(triangle.hit(parallel, 0.001, f64::INFINITY).is_none());
+        assert!(triangle.hit(behind, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn triangle_smooth_normals_and_uvs() {
+        let triangle = unit_triangle()
+            .with_normals([
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 1.0).unit_vector(),
+                Vec3::new(0.0, 1.0, 1.0).unit_vector(),
+            ])
+            .with_uvs([(0.0, 0.0), (2.0, 0.0), (0.0, 4.0)]);
+
+        // Coming from below, the normals must still face the ray.
+        let r = Ray::new(Point3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = triangle.hit(r, 0.001, f64::INFINITY).unwrap();
+
+        assert!(!rec.front_face);
+        assert!(rec.normal.dot(r.direction) < 0.0);
+        assert!((rec.normal.length() - 1.0).abs() < 1e-12);
+        assert!(rec.normal.x < 0.0 && rec.normal.y < 0.0);
+        assert_eq!((rec.u, rec.v), (1.0, 2.0));
+    }
+
+    #[test]
+    fn triangle_light_sampling() {
+        let triangle = unit_triangle();
+        let origin = Point3::new(0.2, 0.3, 0.5);
+
+        // Solid angle of the triangle (Van Oosterom and Strackee).
+        let [a, b, c] = triangle.vertices.map(|v| v - origin);
+        let (la, lb, lc) = (a.length(), b.length(), c.length());
+        let numerator = a.dot(b.cross(c)).abs();
+        let denominator = la * lb * lc + a.dot(b) * lc + a.dot(c) * lb + b.dot(c) * la;
+        let solid_angle = 2.0 * numerator.atan2(denominator);
+
+        // The average of 1 / pdf over sampled directions is the solid angle they cover.
+        let mut rng = StdRng::seed_from_u64(2);
+        let n = 20_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let direction = triangle.random(origin, &mut rng);
+            let pdf = triangle.pdf_value(origin, direction);
+            assert!(pdf > 0.0);
+            sum += 1.0 / pdf;
+        }
+        let estimate = sum / n as f64;
+        assert!(
+            (estimate - solid_angle).abs() < 0.02 * solid_angle,
+            "{} {}",
+            estimate,
+            solid_angle
+        );
+
+        assert_eq!(triangle.pdf_value(origin, Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+}
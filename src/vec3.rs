@@ -1,9 +1,5 @@
-use image::Pixel;
 use rand::prelude::*;
-use std::{
-    cmp::min,
-    ops::{self, Neg},
-};
+use std::ops::{self, Neg};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
@@ -30,13 +26,11 @@ impl Vec3 {
         Self { x, y, z }
     }
 
-    pub fn random() -> Self {
-        Self::random_range(0.0, 1.0)
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::random_range(rng, 0.0, 1.0)
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
-        let mut rng = thread_rng();
-
+    pub fn random_range<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Self {
         Self::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -44,9 +38,9 @@ impl Vec3 {
         )
     }
 
-    pub fn random_in_unit_sphere() -> Self {
+    pub fn random_in_unit_sphere<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
-            let p = Self::random_range(-1.0, 1.0);
+            let p = Self::random_range(rng, -1.0, 1.0);
 
             if p.length_squared() >= 1.0 {
                 continue;
@@ -56,12 +50,12 @@ impl Vec3 {
         }
     }
 
-    pub fn random_unit_vector() -> Self {
-        Self::random_in_unit_sphere().unit_vector()
+    pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::random_in_unit_sphere(rng).unit_vector()
     }
 
-    pub fn random_hemisphere(normal: Self) -> Self {
-        let in_unit_sphere = Self::random_in_unit_sphere();
+    pub fn random_hemisphere<R: Rng + ?Sized>(normal: Self, rng: &mut R) -> Self {
+        let in_unit_sphere = Self::random_in_unit_sphere(rng);
 
         if in_unit_sphere.dot(normal) > 0.0 {
             in_unit_sphere
@@ -113,8 +107,7 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        let mut rng = thread_rng();
+    pub fn random_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
             let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
 
@@ -207,6 +200,19 @@ impl ops::Sub for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", axis),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;